@@ -0,0 +1,124 @@
+use super::{Color, Vec3};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// Critical value of the normal distribution for a 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Smallest luminance used as reference for the relative error, so black pixels can converge.
+const MIN_REFERENCE_LUMINANCE: f64 = 1.0e-3;
+
+/// Running mean and variance of the samples of a single pixel, updated with Welford's online
+/// algorithm (<https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance>).
+///
+/// The mean is tracked for every color channel, the variance only for the luminance.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelStatistics {
+    count: usize,
+    sum: Vec3,
+    mean_luminance: f64,
+    m2: f64,
+}
+
+impl PixelStatistics {
+    pub fn add(&mut self, sample: Color) {
+        self.count += 1;
+        let n = self.count as f64;
+
+        self.sum += sample.into();
+
+        let luminance = sample.luminance();
+        let delta = luminance - self.mean_luminance;
+        self.mean_luminance += delta / n;
+        self.m2 += delta * (luminance - self.mean_luminance);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> Vec3 {
+        self.sum / self.count as f64
+    }
+
+    /// Returns the sample variance of the luminance.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.m2 / (self.count - 1) as f64
+    }
+
+    /// Returns `true` if the half width of the 95% confidence interval of the mean luminance is
+    /// smaller than `threshold` times the mean luminance.
+    pub fn converged(&self, threshold: f64) -> bool {
+        if self.count < 2 {
+            return false;
+        }
+
+        let half_width = Z_95 * (self.variance() / self.count as f64).sqrt();
+        half_width <= threshold * self.mean_luminance.max(MIN_REFERENCE_LUMINANCE)
+    }
+
+    /// Writes the statistics in a binary format that can be read by `read_from`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.count as u64).to_le_bytes())?;
+        for value in [
+            self.sum.x(),
+            self.sum.y(),
+            self.sum.z(),
+            self.mean_luminance,
+            self.m2,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buffer = [0; 8];
+        let mut next = || reader.read_exact(&mut buffer).map(|_| buffer);
+
+        let count = u64::from_le_bytes(next()?) as usize;
+        let mut values = [0.0; 5];
+        for value in &mut values {
+            *value = f64::from_le_bytes(next()?);
+        }
+
+        Ok(Self {
+            count,
+            sum: Vec3::new(values[0], values[1], values[2]),
+            mean_luminance: values[3],
+            m2: values[4],
+        })
+    }
+}
+
+/// Writes a PPM image that shows how many samples every pixel used, going from black (no
+/// samples) over red and yellow to white (`max_samples`).
+///
+/// Arguments:
+///
+/// * `sample_counts`: Number of samples of every pixel, in the order they are written to the image.
+pub fn write_heatmap(
+    path: &Path,
+    width: usize,
+    height: usize,
+    sample_counts: &[usize],
+    max_samples: usize,
+) -> io::Result<()> {
+    let mut ppm = format!("P3\n{} {}\n255\n", width, height);
+
+    for &count in sample_counts {
+        let t = 3.0 * count as f64 / max_samples as f64;
+        let channel = |offset: f64| ((t - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        ppm += &format!("{} {} {}\n", channel(0.0), channel(1.0), channel(2.0));
+    }
+
+    fs::write(path, ppm)
+}
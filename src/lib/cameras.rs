@@ -0,0 +1,651 @@
+use super::{
+    sampling,
+    traits::{Camera, Sampler},
+    Point3, Ray, Vec3,
+};
+use std::{f64::consts::PI, str::FromStr};
+
+/// Size of the view of an orthographic camera, given by one side. The other side follows from
+/// the aspect ratio.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ViewSize {
+    Width(f64),
+    Height(f64),
+}
+
+impl FromStr for ViewSize {
+    type Err = String;
+
+    /// Parses `width=<size>` or `height=<size>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "invalid view size `{}` (expected `width=<size>` or `height=<size>`)",
+                s
+            )
+        };
+        let (side, size) = s.split_once('=').ok_or_else(error)?;
+        let size: f64 = size.parse().map_err(|_| error())?;
+        if size <= 0.0 {
+            return Err("view size should be larger than 0".to_string());
+        }
+
+        match side {
+            "width" => Ok(Self::Width(size)),
+            "height" => Ok(Self::Height(size)),
+            _ => Err(error()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Projection {
+    Perspective,
+    /// Parallel rays along `forward`, which reaches from the view port to the plane in focus
+    Orthographic {
+        forward: Vec3,
+    },
+}
+
+/// Pinhole or thin lens camera with a perspective or orthographic projection onto a rectangular
+/// view port.
+#[derive(Debug, Copy, Clone)]
+pub struct ThinLensCamera {
+    origin: Point3,
+    lower_left_corner: Point3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    lens_radius: f64,
+    projection: Projection,
+}
+
+impl ThinLensCamera {
+    /// Returns a new instance of the `ThinLensCamera` struct, with position and direction set to `(0, 0, 0)` looking at the negative z axes.
+    ///
+    /// Arguments:
+    ///
+    /// * `vfov`: Vertical angle (in degrees) from the camera origin to the top and bottom of the view port.
+    /// * `aspect_ratio`: Ratio of width devided by height of the view port.
+    pub fn new(vfov: f64, aspect_ratio: f64) -> Self {
+        let theta = f64::to_radians(vfov);
+        let h = f64::tan(theta / 2.0);
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let focal_length = 1.0;
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let horizontal = Vec3::new(viewport_width, 0.0, 0.0);
+        let vertical = Vec3::new(0.0, viewport_height, 0.0);
+        let lower_left_corner =
+            origin - horizontal / 2.0 - vertical / 2.0 - Vec3::new(0.0, 0.0, focal_length);
+
+        Self {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens_radius: 0.0,
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Returns a new instance of the `ThinLensCamera` struct, with aperture set to `0` and thus everything
+    /// is within focus.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: Origin of the camera.
+    /// * `lookat`: Point the camera is looking at.
+    /// * `vup`: Vector indication the up acces of the camera (used to specify roll).
+    /// * `vfov`: Vertical angle (in degrees) from the camera origin to the top and bottom of the view port.
+    /// * `aspect_ratio`: Ratio of width devided by height of the view port.
+    pub fn new_positional(
+        origin: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        vfov: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        let theta = f64::to_radians(vfov);
+        let h = f64::tan(theta / 2.0);
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = (origin - lookat).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+
+        let horizontal = viewport_width * u;
+        let vertical = viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w;
+
+        Self {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens_radius: 0.0,
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Returns a new instance of the `ThinLensCamera` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: Origin of the camera.
+    /// * `lookat`: Point the camera is looking at.
+    /// * `vup`: Vector indication the up acces of the camera (used to specify roll).
+    /// * `vfov`: Vertical angle (in degrees) from the camera origin to the top and bottom of the view port.
+    /// * `aspect_ratio`: Ratio of width devided by height of the view port.
+    /// * `aperture`: Size of the lens. This is used as a possible offset for the start of a `Ray`,
+    ///   thus a _larger_ aperture results in more focus blur.
+    pub fn new_focusable(
+        origin: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        vfov: f64,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> Self {
+        let theta = f64::to_radians(vfov);
+        let h = f64::tan(theta / 2.0);
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = (origin - lookat).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+
+        let horizontal = focus_dist * viewport_width * u;
+        let vertical = focus_dist * viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
+
+        Self {
+            origin,
+            horizontal,
+            vertical,
+            lower_left_corner,
+            lens_radius: aperture / 2.0,
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Returns a new instance of the `ThinLensCamera` struct with an orthographic projection, where all
+    /// rays are parallel and objects keep their size at any distance, e.g. for technical or
+    /// isometric illustrations.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: Center of the view port.
+    /// * `lookat`: Point the camera is looking at.
+    /// * `vup`: Vector indication the up acces of the camera (used to specify roll).
+    /// * `view`: Width or height of the view port in scene units.
+    /// * `aspect_ratio`: Ratio of width devided by height of the view port.
+    /// * `aperture`: Size of the lens, `0` keeps everything in focus. Rays start on the lens and
+    ///   meet at the plane at `focus_dist` in front of the view port.
+    pub fn new_orthographic(
+        origin: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        view: ViewSize,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> Self {
+        let (viewport_width, viewport_height) = match view {
+            ViewSize::Width(width) => (width, width / aspect_ratio),
+            ViewSize::Height(height) => (aspect_ratio * height, height),
+        };
+
+        let w = (origin - lookat).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+
+        let horizontal = viewport_width * u;
+        let vertical = viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0;
+
+        Self {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens_radius: aperture / 2.0,
+            projection: Projection::Orthographic {
+                forward: -focus_dist * w,
+            },
+        }
+    }
+}
+
+impl ThinLensCamera {
+    /// Returns the cameras of the left and right eye, `interocular` apart to the sides of this
+    /// camera. Objects at `convergence_distance` in front of the camera appear at the same
+    /// position in both images and thus at the depth of the screen.
+    pub fn stereo_pair(
+        &self,
+        interocular: f64,
+        convergence_distance: f64,
+        convergence: Convergence,
+    ) -> (Self, Self) {
+        let center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        let focus_dist = (center - self.origin).length();
+        let forward = (center - self.origin) / focus_dist;
+        let right = self.horizontal.unit_vector();
+
+        let eye = |offset: f64| {
+            let origin = self.origin + offset * right;
+            match convergence {
+                // parallel eyes, with the view ports shifted towards each other until they
+                // overlap at the convergence distance
+                Convergence::OffAxis => Self {
+                    origin,
+                    lower_left_corner: self.lower_left_corner
+                        + offset * (1.0 - focus_dist / convergence_distance) * right,
+                    ..*self
+                },
+                // eyes turned towards the point at the convergence distance
+                Convergence::ToeIn => {
+                    let length = convergence_distance.hypot(offset);
+                    let eye_forward = (convergence_distance * forward - offset * right) / length;
+                    let eye_right = (convergence_distance * right + offset * forward) / length;
+                    let horizontal = self.horizontal.length() * eye_right;
+                    Self {
+                        origin,
+                        lower_left_corner: origin + focus_dist * eye_forward
+                            - horizontal / 2.0
+                            - self.vertical / 2.0,
+                        horizontal,
+                        ..*self
+                    }
+                }
+            }
+        };
+
+        (eye(-interocular / 2.0), eye(interocular / 2.0))
+    }
+}
+
+impl Camera for ThinLensCamera {
+    /// Returns the ray through the view port position `(u, v)`. The position on the lens is taken
+    /// from the next 2D dimension of the `sampler`.
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let rc = self.lens_radius * sampling::sample_uniform_disk_concentric(sampler.get_2d());
+        let offset = self.horizontal.unit_vector() * rc.x() + self.vertical.unit_vector() * rc.y();
+        let target = self.lower_left_corner + u * self.horizontal + v * self.vertical;
+
+        Some(match self.projection {
+            Projection::Perspective => {
+                Ray::new(self.origin + offset, target - self.origin - offset)
+            }
+            // the ray starts at its own point of the view port and aims at the focus plane
+            Projection::Orthographic { forward } => Ray::new(target + offset, forward - offset),
+        })
+    }
+}
+
+impl Default for ThinLensCamera {
+    fn default() -> Self {
+        let aspect_ratio = 16_f64 / 9f64;
+        let viewport_height = 2_f64;
+        let viewport_width = aspect_ratio * viewport_height;
+        let focal_length = 1_f64;
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let horizontal = Vec3::new(viewport_width, 0.0, 0.0);
+        let vertical = Vec3::new(0.0, viewport_height, 0.0);
+        let lower_left_corner =
+            origin - horizontal / 2.0 - vertical / 2.0 - Vec3::new(0.0, 0.0, focal_length);
+
+        Self {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens_radius: 0.0,
+            projection: Projection::Perspective,
+        }
+    }
+}
+
+/// Axes of a camera that looks from `origin` at `lookat`, as right, up and forward unit vectors.
+fn view_frame(origin: Point3, lookat: Point3, vup: Vec3) -> (Vec3, Vec3, Vec3) {
+    let forward = (lookat - origin).unit_vector();
+    let right = forward.cross(vup).unit_vector();
+    let up = right.cross(forward);
+    (right, up, forward)
+}
+
+/// Camera that sees all directions around it (360 by 180 degrees) in an equirectangular
+/// projection, e.g. for environment probes and VR panoramas. The image should have an aspect
+/// ratio of 2.
+///
+/// The horizontal axis of the image is the longitude, with the direction the camera looks at in
+/// the center, and the vertical axis the latitude from straight down to straight up.
+#[derive(Debug, Copy, Clone)]
+pub struct EquirectangularCamera {
+    origin: Point3,
+    right: Vec3,
+    up: Vec3,
+    forward: Vec3,
+    /// Distance of the ray origins from `origin` to the right of the horizontal direction, for
+    /// one eye of an omni-directional stereo panorama
+    eye_offset: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(origin: Point3, lookat: Point3, vup: Vec3) -> Self {
+        let (right, up, forward) = view_frame(origin, lookat, vup);
+        Self {
+            origin,
+            right,
+            up,
+            forward,
+            eye_offset: 0.0,
+        }
+    }
+
+    /// Returns the cameras of the left and right eye of an omni-directional stereo (ODS)
+    /// panorama. Every ray starts on the circle with a diameter of `interocular` around the
+    /// origin, where the eye would be when looking into the direction of the ray.
+    pub fn ods_pair(&self, interocular: f64) -> (Self, Self) {
+        let eye = |eye_offset: f64| Self {
+            eye_offset,
+            ..*self
+        };
+        (eye(-interocular / 2.0), eye(interocular / 2.0))
+    }
+
+    /// Returns the unit direction at the image position `(u, v)`.
+    fn direction(&self, u: f64, v: f64) -> Vec3 {
+        let (sin_phi, cos_phi) = ((u - 0.5) * 2.0 * PI).sin_cos();
+        let (sin_theta, cos_theta) = ((v - 0.5) * PI).sin_cos();
+        cos_theta * (sin_phi * self.right + cos_phi * self.forward) + sin_theta * self.up
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, u: f64, v: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (sin_phi, cos_phi) = ((u - 0.5) * 2.0 * PI).sin_cos();
+        let origin =
+            self.origin + self.eye_offset * (cos_phi * self.right - sin_phi * self.forward);
+        Some(Ray::new(origin, self.direction(u, v)))
+    }
+}
+
+/// Mapping from the angle to the optical axis to the distance from the center of a fisheye
+/// image, parsed from its lowercase name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FisheyeProjection {
+    /// The distance grows linearly with the angle.
+    Equidistant,
+    /// Equal areas of the image cover equal solid angles.
+    Equisolid,
+}
+
+impl FromStr for FisheyeProjection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equidistant" => Ok(Self::Equidistant),
+            "equisolid" => Ok(Self::Equisolid),
+            _ => Err(format!(
+                "unknown fisheye projection `{}` (expected `equidistant` or `equisolid`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Camera with a fisheye lens, which shows the field of view in a circle that touches the top
+/// and bottom of the image. Positions outside of the circle stay black.
+#[derive(Debug, Copy, Clone)]
+pub struct FisheyeCamera {
+    origin: Point3,
+    right: Vec3,
+    up: Vec3,
+    forward: Vec3,
+    projection: FisheyeProjection,
+    /// Largest angle to the optical axis in radians, at the edge of the image circle
+    theta_max: f64,
+    aspect_ratio: f64,
+}
+
+impl FisheyeCamera {
+    /// Returns a new instance of the `FisheyeCamera` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `origin`: Origin of the camera.
+    /// * `lookat`: Point the camera is looking at.
+    /// * `vup`: Vector indication the up acces of the camera (used to specify roll).
+    /// * `fov`: Angle (in degrees) across the image circle, up to `360`.
+    /// * `aspect_ratio`: Ratio of width devided by height of the image.
+    pub fn new(
+        origin: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        projection: FisheyeProjection,
+        fov: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        assert!(
+            fov > 0.0 && fov <= 360.0,
+            "field of view should be between 0 and 360 degrees"
+        );
+
+        let (right, up, forward) = view_frame(origin, lookat, vup);
+        Self {
+            origin,
+            right,
+            up,
+            forward,
+            projection,
+            theta_max: fov.to_radians() / 2.0,
+            aspect_ratio,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, u: f64, v: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        // position relative to the image circle, with a radius of 1
+        let x = (2.0 * u - 1.0) * self.aspect_ratio;
+        let y = 2.0 * v - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.theta_max,
+            FisheyeProjection::Equisolid => 2.0 * (r * (self.theta_max / 2.0).sin()).asin(),
+        };
+        let (cos_psi, sin_psi) = match r > 0.0 {
+            true => (x / r, y / r),
+            false => (1.0, 0.0),
+        };
+
+        let direction =
+            theta.sin() * (cos_psi * self.right + sin_psi * self.up) + theta.cos() * self.forward;
+        Some(Ray::new(self.origin, direction))
+    }
+}
+
+/// Camera that renders the six faces of a cube around it, each with a 90 degree field of view,
+/// e.g. for environment probes. The image should have an aspect ratio of 3:2.
+///
+/// The faces are laid out in two rows, `+x`, `-x`, `+y` on top and `-y`, `+z`, `-z` at the
+/// bottom, named by the axis they look along. The side faces have `+y` up, the top face (`+y`)
+/// has `+z` up and the bottom face (`-y`) `-z`.
+#[derive(Debug, Copy, Clone)]
+pub struct CubeMapCamera {
+    origin: Point3,
+}
+
+impl CubeMapCamera {
+    pub fn new(origin: Point3) -> Self {
+        Self { origin }
+    }
+}
+
+impl Camera for CubeMapCamera {
+    fn get_ray(&self, u: f64, v: f64, _sampler: &mut dyn Sampler) -> Option<Ray> {
+        let column = ((u * 3.0) as usize).min(2);
+        let row = (((1.0 - v) * 2.0) as usize).min(1);
+        let (forward, up) = cube_face(3 * row + column);
+
+        // position on the face in `[-1, 1]`
+        let s = 2.0 * (u * 3.0 - column as f64) - 1.0;
+        let t = 2.0 * (v * 2.0 - (1 - row) as f64) - 1.0;
+
+        let right = forward.cross(up);
+        Some(Ray::new(self.origin, forward + s * right + t * up))
+    }
+}
+
+/// Returns the forward and up axis of the cube map face at `index` in the layout.
+fn cube_face(index: usize) -> (Vec3, Vec3) {
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    match index {
+        0 => (Vec3::new(1.0, 0.0, 0.0), y),
+        1 => (Vec3::new(-1.0, 0.0, 0.0), y),
+        2 => (y, Vec3::new(0.0, 0.0, 1.0)),
+        3 => (-y, Vec3::new(0.0, 0.0, -1.0)),
+        4 => (Vec3::new(0.0, 0.0, 1.0), y),
+        _ => (Vec3::new(0.0, 0.0, -1.0), y),
+    }
+}
+
+/// The available camera projections, parsed from their lowercase name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CameraKind {
+    Perspective,
+    Equirectangular,
+    Fisheye(FisheyeProjection),
+    CubeMap,
+}
+
+impl CameraKind {
+    /// Returns the aspect ratio of the image the camera is made for, or `None` if it works with
+    /// any.
+    pub fn aspect_ratio(&self) -> Option<f64> {
+        match self {
+            Self::Equirectangular => Some(2.0),
+            Self::CubeMap => Some(1.5),
+            Self::Perspective | Self::Fisheye(_) => None,
+        }
+    }
+}
+
+impl FromStr for CameraKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perspective" => Ok(Self::Perspective),
+            "equirectangular" => Ok(Self::Equirectangular),
+            "equidistant" => Ok(Self::Fisheye(FisheyeProjection::Equidistant)),
+            "equisolid" => Ok(Self::Fisheye(FisheyeProjection::Equisolid)),
+            "cubemap" => Ok(Self::CubeMap),
+            _ => Err(format!(
+                "unknown camera `{}` (expected `perspective`, `equirectangular`, `equidistant`, `equisolid` or `cubemap`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Way the eyes of a stereo camera converge, parsed from its lowercase name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Convergence {
+    /// The eyes look in parallel and their view ports are shifted, which keeps vertical lines
+    /// aligned between both images.
+    OffAxis,
+    /// The eyes are turned towards each other, which causes vertical parallax at the sides of
+    /// the images.
+    ToeIn,
+}
+
+impl FromStr for Convergence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off-axis" => Ok(Self::OffAxis),
+            "toe-in" => Ok(Self::ToeIn),
+            _ => Err(format!(
+                "unknown convergence `{}` (expected `off-axis` or `toe-in`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Arrangement of the images of both eyes, parsed from its lowercase name.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// The left eye on the left half and the right eye on the right half of the image.
+    SideBySide,
+    /// The left eye on the top half and the right eye on the bottom half of the image.
+    TopBottom,
+}
+
+impl StereoLayout {
+    /// Returns the aspect ratio of the whole image when each eye has `eye_aspect_ratio`.
+    pub fn aspect_ratio(&self, eye_aspect_ratio: f64) -> f64 {
+        match self {
+            Self::SideBySide => 2.0 * eye_aspect_ratio,
+            Self::TopBottom => eye_aspect_ratio / 2.0,
+        }
+    }
+}
+
+impl FromStr for StereoLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "side-by-side" => Ok(Self::SideBySide),
+            "top-bottom" => Ok(Self::TopBottom),
+            _ => Err(format!(
+                "unknown stereo layout `{}` (expected `side-by-side` or `top-bottom`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Camera that renders the views of a `left` and `right` eye next to each other into one image,
+/// e.g. for VR headsets and 3D displays.
+#[derive(Debug, Copy, Clone)]
+pub struct StereoCamera<C: Camera> {
+    left: C,
+    right: C,
+    layout: StereoLayout,
+}
+
+impl<C: Camera> StereoCamera<C> {
+    pub fn new((left, right): (C, C), layout: StereoLayout) -> Self {
+        Self {
+            left,
+            right,
+            layout,
+        }
+    }
+}
+
+impl<C: Camera> Camera for StereoCamera<C> {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if u < 0.5 => self.left.get_ray(2.0 * u, v, sampler),
+            StereoLayout::SideBySide => self.right.get_ray(2.0 * u - 1.0, v, sampler),
+            StereoLayout::TopBottom if v >= 0.5 => self.left.get_ray(u, 2.0 * v - 1.0, sampler),
+            StereoLayout::TopBottom => self.right.get_ray(u, 2.0 * v, sampler),
+        }
+    }
+}
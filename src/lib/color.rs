@@ -1,58 +1,50 @@
-use super::Vec3;
+use super::{display::DisplayPipeline, Vec3};
 use std::{
     fmt::Display,
     ops::{Add, AddAssign, Mul, MulAssign},
 };
 
+/// Linear RGB radiance. Channels are not bounded above, the `DisplayPipeline` maps them to the
+/// range of the display.
 #[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub struct Color(f64, f64, f64);
 
 impl Color {
     pub fn new(red: f64, green: f64, blue: f64) -> Self {
-        assert!(
-            (0f64..=1f64).contains(&red)
-                && (0f64..=1f64).contains(&green)
-                && (0f64..=1f64).contains(&blue)
-        );
+        assert!(red >= 0.0 && green >= 0.0 && blue >= 0.0);
         Self(red, green, blue)
     }
 
     pub fn red(&self) -> f64 {
-        assert!((0f64..=1f64).contains(&self.0));
+        assert!(self.0 >= 0.0);
         self.0
     }
 
     pub fn green(&self) -> f64 {
-        assert!((0f64..=1f64).contains(&self.1));
+        assert!(self.1 >= 0.0);
         self.1
     }
 
     pub fn blue(&self) -> f64 {
-        assert!((0f64..=1f64).contains(&self.2));
+        assert!(self.2 >= 0.0);
         self.2
     }
 
     pub fn valid(&self) -> bool {
-        (0f64..=1f64).contains(&self.0)
-            && (0f64..=1f64).contains(&self.1)
-            && (0f64..=1f64).contains(&self.2)
+        self.0 >= 0.0 && self.1 >= 0.0 && self.2 >= 0.0
     }
 
-    pub fn write_color(&self) -> String {
-        assert!(self.valid());
+    /// Returns the relative luminance of the color (Rec. 709 weights).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
 
-        // gamma correct before output
-        let gamma_inv = 1.0 / 2.0;
-        let r = self.0.powf(gamma_inv);
-        let g = self.1.powf(gamma_inv);
-        let b = self.2.powf(gamma_inv);
+    /// Returns the color as `r g b` line of a PPM image, see `DisplayPipeline::quantize`.
+    pub fn write_color(&self, display: &DisplayPipeline, pixel: (usize, usize)) -> String {
+        assert!(self.valid());
 
-        format!(
-            "{} {} {}",
-            (r * 255.0).round() as u8,
-            (g * 255.0).round() as u8,
-            (b * 255.0).round() as u8
-        )
+        let [r, g, b] = display.quantize(*self, pixel);
+        format!("{} {} {}", r, g, b)
     }
 }
 
@@ -119,11 +111,11 @@ impl TryFrom<Vec3> for Color {
     type Error = String;
 
     fn try_from(value: Vec3) -> Result<Self, Self::Error> {
-        if !((0f64..=1f64).contains(&value.x())
-            && (0f64..=1f64).contains(&value.y())
-            && (0f64..=1f64).contains(&value.z()))
-        {
-            return Err("could not convert `Vec3` to `Color`: `x`, `y` or `z` values are not contained in the `(0..=1)` range".to_string());
+        if value.x() < 0.0 || value.y() < 0.0 || value.z() < 0.0 {
+            return Err(
+                "could not convert `Vec3` to `Color`: `x`, `y` or `z` values are negative"
+                    .to_string(),
+            );
         }
 
         Ok(Self(value.x(), value.y(), value.z()))
@@ -0,0 +1,248 @@
+use super::{utils, Color};
+use std::str::FromStr;
+
+/// Maps the unbounded radiance of a pixel to the `[0, 1]` range of a display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapper {
+    /// Clips every channel at 1.
+    Clamp,
+    /// `L / (1 + L)` on the luminance (Reinhard et al., "Photographic Tone Reproduction for
+    /// Digital Images").
+    Reinhard,
+    /// Reinhard operator where a luminance of `white` (and above) maps to 1.
+    ExtendedReinhard { white: f64 },
+    /// Stephen Hill's fit of the ACES reference rendering and output device transforms.
+    Aces,
+    /// Troy Sobotka's AgX with the default look, using Benjamin Wrensch's polynomial fit.
+    Agx,
+}
+
+impl ToneMapper {
+    /// Returns the tone mapped color as `[red, green, blue]`, with every channel in `[0, 1]`.
+    pub fn apply(&self, color: [f64; 3]) -> [f64; 3] {
+        let mapped = match self {
+            Self::Clamp => color,
+            Self::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            Self::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            Self::Aces => aces(color),
+            Self::Agx => agx(color),
+        };
+
+        mapped.map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    /// Parses the lowercase name of the tone mapper. The white point of the extended Reinhard
+    /// operator can be given after a colon (`extended-reinhard:8`), it defaults to `4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("extended-reinhard", white)) => match white.parse() {
+                Ok(white) if white > 0.0 => Ok(Self::ExtendedReinhard { white }),
+                _ => Err(format!("invalid white point `{}`", white)),
+            },
+            Some(_) => Err(format!("tone mapper `{}` does not take a parameter", s)),
+            None => match s {
+                "clamp" => Ok(Self::Clamp),
+                "reinhard" => Ok(Self::Reinhard),
+                "extended-reinhard" => Ok(Self::ExtendedReinhard { white: 4.0 }),
+                "aces" => Ok(Self::Aces),
+                "agx" => Ok(Self::Agx),
+                _ => Err(format!(
+                    "unknown tone mapper `{}` (expected `clamp`, `reinhard`, `extended-reinhard`, `aces` or `agx`)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// Encodes the linear, tone mapped values for the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFunction {
+    /// Exact piecewise sRGB curve (IEC 61966-2-1).
+    Srgb,
+    /// Pure power curve with a gamma of 2.2.
+    Gamma22,
+    /// No encoding, the linear values are written as is.
+    Linear,
+}
+
+impl TransferFunction {
+    pub fn encode(&self, value: f64) -> f64 {
+        match self {
+            Self::Srgb if value <= 0.003_130_8 => 12.92 * value,
+            Self::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            Self::Gamma22 => value.powf(1.0 / 2.2),
+            Self::Linear => value,
+        }
+    }
+
+    /// Inverse of `encode`, turns a display encoded value back into a linear value.
+    pub fn decode(&self, value: f64) -> f64 {
+        match self {
+            Self::Srgb if value <= 0.040_45 => value / 12.92,
+            Self::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Self::Gamma22 => value.powf(2.2),
+            Self::Linear => value,
+        }
+    }
+}
+
+impl FromStr for TransferFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "srgb" => Ok(Self::Srgb),
+            "gamma2.2" => Ok(Self::Gamma22),
+            "linear" => Ok(Self::Linear),
+            _ => Err(format!(
+                "unknown transfer function `{}` (expected `srgb`, `gamma2.2` or `linear`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Turns the radiance of a pixel into an 8 bit color: exposure, tone mapping, transfer function
+/// and quantization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayPipeline {
+    /// Exposure adjustment in stops, the radiance is multiplied by `2^exposure`.
+    pub exposure: f64,
+    pub tone_mapper: ToneMapper,
+    pub transfer: TransferFunction,
+    /// Adds a small amount of noise before quantizing, which hides banding in smooth gradients.
+    pub dither: bool,
+}
+
+impl DisplayPipeline {
+    /// Returns the display encoded color as `[red, green, blue]`, with every channel in `[0, 1]`.
+    pub fn apply(&self, color: Color) -> [f64; 3] {
+        let scale = self.exposure.exp2();
+        let color = [color.red(), color.green(), color.blue()].map(|c| c * scale);
+
+        self.tone_mapper
+            .apply(color)
+            .map(|c| self.transfer.encode(c))
+    }
+
+    /// Returns the 8 bit color of the pixel at image coordinates `pixel`. The position is only
+    /// used to seed the dither, so the same pixel always gets the same noise.
+    pub fn quantize(&self, color: Color, pixel: (usize, usize)) -> [u8; 3] {
+        let encoded = self.apply(color);
+
+        let mut quantized = [0; 3];
+        for (channel, (value, out)) in encoded.iter().zip(&mut quantized).enumerate() {
+            // triangular noise of at most one quantization step
+            let noise = match self.dither {
+                true => {
+                    let hash = utils::hash(&[pixel.0 as u64, pixel.1 as u64, channel as u64]);
+                    utils::hash_to_double(hash) + utils::hash_to_double(utils::mix_bits(hash)) - 1.0
+                }
+                false => 0.0,
+            };
+
+            *out = (value * 255.0 + noise).round().clamp(0.0, 255.0) as u8;
+        }
+
+        quantized
+    }
+}
+
+impl Default for DisplayPipeline {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapper: ToneMapper::Clamp,
+            transfer: TransferFunction::Srgb,
+            dither: true,
+        }
+    }
+}
+
+/// Scales the color so its luminance is mapped by `map`, which keeps the hue intact.
+fn scale_luminance(color: [f64; 3], map: impl Fn(f64) -> f64) -> [f64; 3] {
+    let luminance = Color::new(color[0], color[1], color[2]).luminance();
+    if luminance <= 0.0 {
+        return [0.0; 3];
+    }
+
+    let scale = map(luminance) / luminance;
+    color.map(|c| c * scale)
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn aces(color: [f64; 3]) -> [f64; 3] {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let fitted = multiply(&INPUT, color).map(|v| {
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081)
+    });
+    multiply(&OUTPUT, fitted)
+}
+
+fn agx(color: [f64; 3]) -> [f64; 3] {
+    const INSET: [[f64; 3]; 3] = [
+        [
+            0.842_479_062_253_094,
+            0.078_433_599_999_999_2,
+            0.079_223_745_147_764_3,
+        ],
+        [
+            0.042_328_242_261_012_3,
+            0.878_468_636_469_772,
+            0.079_166_127_460_543_4,
+        ],
+        [0.042_375_654_905_705_1, 0.078_433_6, 0.879_142_973_793_104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [
+            1.196_879_005_120_17,
+            -0.098_020_881_140_136_8,
+            -0.099_029_744_079_720_5,
+        ],
+        [
+            -0.052_896_851_757_456_2,
+            1.151_903_129_904_17,
+            -0.098_961_176_844_843_3,
+        ],
+        [
+            -0.052_971_635_514_443_8,
+            -0.098_043_450_117_124_1,
+            1.151_073_672_641_16,
+        ],
+    ];
+    const MIN_EV: f64 = -12.473_93;
+    const MAX_EV: f64 = 4.026_069;
+
+    let encoded = multiply(&INSET, color).map(|v| {
+        // log2 encoding, followed by the sigmoid contrast curve
+        let x = (v.max(1.0e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+
+    // the curve outputs display encoded values, decode them so the transfer function applies
+    multiply(&OUTSET, encoded).map(|v| v.max(0.0).powf(2.2))
+}
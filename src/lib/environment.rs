@@ -0,0 +1,562 @@
+use super::{
+    display::TransferFunction,
+    image::Image,
+    lights::LightSample,
+    sampling::{self, Distribution2D},
+    spectrum,
+    traits::{Environment, Sampler},
+    Color, Onb, Vec3,
+};
+use std::{f64::consts::PI, io, path::Path, str::FromStr};
+
+/// Sky that blends from one color at the bottom to another at the top.
+pub struct GradientSky {
+    bottom: Color,
+    top: Color,
+}
+
+impl GradientSky {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl Default for GradientSky {
+    /// White to light blue.
+    fn default() -> Self {
+        Self::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for GradientSky {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let t = 0.5 * (direction.unit_vector().y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// Environment from an equirectangular (latitude-longitude) image, usually a high dynamic range
+/// photo of a real environment (HDRI). The center of the image is seen looking along `-z`, the
+/// top row is straight up.
+///
+/// Directions are importance sampled proportional to the luminance of the pixels, so a small and
+/// bright sun in the image lights the scene without much noise.
+pub struct EnvironmentMap {
+    image: Image,
+    /// Rotation around the `y` axis in radians
+    rotation: f64,
+    /// Scale of the radiance of the image
+    intensity: f64,
+    /// Distribution of the pixels, proportional to their luminance and solid angle
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let luminance: Vec<f64> = (0..height)
+            .flat_map(|y| {
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+                let image = &image;
+                (0..width).map(move |x| image.pixel(x, y).luminance().max(0.0) * sin_theta)
+            })
+            .collect();
+
+        Self {
+            distribution: Distribution2D::new(&luminance, width),
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    /// Loads a Radiance HDR (`.hdr`) or sRGB encoded PPM image.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let image = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("hdr") => Image::read_hdr(path)?,
+            _ => Image::read_ppm(path, TransferFunction::Srgb)?,
+        };
+        Ok(Self::new(image))
+    }
+
+    /// Rotates the environment counter-clockwise around the `y` (up) axis by `degrees`.
+    pub fn rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    /// Scales the radiance of the image.
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        assert!(intensity >= 0.0, "intensity cannot be negative");
+        self.intensity = intensity;
+        self
+    }
+
+    /// Rotates `v` around the `y` axis by `angle` radians.
+    fn rotate(v: Vec3, angle: f64) -> Vec3 {
+        let (sin, cos) = angle.sin_cos();
+        Vec3::new(v.x() * cos + v.z() * sin, v.y(), -v.x() * sin + v.z() * cos)
+    }
+
+    /// Returns the image coordinates of `direction`, with `(0, 0)` at the top left.
+    fn image_coordinates(&self, direction: Vec3) -> (f64, f64) {
+        let d = Self::rotate(direction.unit_vector(), -self.rotation);
+        let u = 0.5 + d.x().atan2(-d.z()) / (2.0 * PI);
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+
+        (u.rem_euclid(1.0), v)
+    }
+
+    /// Returns the radiance of the pixel at image coordinates `(u, v)`.
+    fn lookup(&self, (u, v): (f64, f64)) -> Color {
+        let x = ((u * self.image.width() as f64) as usize).min(self.image.width() - 1);
+        let y = ((v * self.image.height() as f64) as usize).min(self.image.height() - 1);
+        self.image.pixel(x, y) * self.intensity
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Color {
+        self.lookup(self.image_coordinates(direction))
+    }
+
+    fn importance_sampled(&self) -> bool {
+        self.intensity > 0.0
+    }
+
+    fn sample_li(&self, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let ((u, v), pdf) = self.distribution.sample(sampler.get_2d());
+        let (sin_theta, cos_theta) = (PI * v).sin_cos();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        let (sin_phi, cos_phi) = (2.0 * PI * (u - 0.5)).sin_cos();
+        let local = Vec3::new(sin_theta * sin_phi, cos_theta, -sin_theta * cos_phi);
+
+        Some(LightSample {
+            radiance: self.lookup((u, v)),
+            wi: Self::rotate(local, self.rotation),
+            distance: f64::INFINITY,
+            // the image covers 2 pi by pi radians, squeezed by sin(theta) towards the poles
+            pdf: pdf / (2.0 * PI * PI * sin_theta),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.image_coordinates(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf((u, v)) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn power(&self, scene_radius: f64) -> f64 {
+        // the distribution averages the luminance times sin(theta) over 2 pi by pi radians
+        let luminance = self.distribution.integral() * 2.0 * PI * PI * self.intensity;
+        PI * scene_radius * scene_radius * luminance
+    }
+}
+
+/// Angular radius of the sun in radians (an angular diameter of 0.53 degrees).
+const SUN_ANGULAR_RADIUS: f64 = 0.004_625;
+/// Luminance of the sun outside of the atmosphere in kcd/m^2.
+const SUN_LUMINANCE: f64 = 2.0e6;
+/// Scale from luminance in kcd/m^2 to radiance, chosen so that a white surface lit by the sun is
+/// around 1.
+const SKY_RADIANCE_SCALE: f64 = 0.05;
+/// Wavelengths (in micrometers) for the atmospheric extinction of the red, green and blue
+/// channel of the sun.
+const SUN_CHANNEL_WAVELENGTHS: [f64; 3] = [0.65, 0.57, 0.475];
+/// Number of rings and segments of the grid used to integrate the sky.
+const SKY_INTEGRATION_STEPS: (usize, usize) = (32, 64);
+/// Largest probability of sampling the sun instead of the whole sky.
+const MAX_SUN_PROBABILITY: f64 = 0.9;
+
+/// Clear sky after the analytic model of Preetham et al. ("A Practical Analytic Model for
+/// Daylight"), with the sun as a bright disk and a diffuse ground below the horizon.
+///
+/// The sky is importance sampled: directions towards the sun are chosen with a probability that
+/// depends on its share of the light, the rest is sampled uniformly.
+pub struct PhysicalSky {
+    /// Unit direction towards the sun
+    sun_direction: Vec3,
+    /// Coefficients of the Perez distribution of the luminance `Y` and chromaticities `x`, `y`
+    perez: [[f64; 5]; 3],
+    /// `Y`, `x` and `y` at the zenith, divided by the Perez function at the zenith
+    zenith: [f64; 3],
+    /// Radiance of the sun disk, `None` below the horizon
+    sun_radiance: Option<Color>,
+    ground_albedo: Color,
+    /// Irradiance of the sun and sky on the ground
+    ground_irradiance: Color,
+    /// Probability of sampling the sun
+    sun_probability: f64,
+    /// Luminance of the sky and the sun integrated over all directions
+    luminance: f64,
+}
+
+impl PhysicalSky {
+    /// Returns a new instance of the `PhysicalSky` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `elevation`: Angle of the sun above the horizon in degrees. Below the horizon the sun is
+    ///   hidden and the sky is the one of a sunset.
+    /// * `azimuth`: Direction of the sun in degrees, clockwise from north (`-z`) towards east
+    ///   (`+x`).
+    /// * `turbidity`: Haziness of the atmosphere in `[1.7, 10]`, from a very clear sky (`2`)
+    ///   over a clear (`3`) to a hazy sky (`10`).
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        assert!(
+            (1.7..=10.0).contains(&turbidity),
+            "turbidity should be between 1.7 and 10"
+        );
+
+        let (sin_azimuth, cos_azimuth) = azimuth.to_radians().sin_cos();
+        let (sin_elevation, cos_elevation) = elevation.to_radians().sin_cos();
+        let sun_direction = Vec3::new(
+            sin_azimuth * cos_elevation,
+            sin_elevation,
+            -cos_azimuth * cos_elevation,
+        );
+
+        // the model only covers a sun above the horizon
+        let theta_s = (PI / 2.0 - elevation.to_radians()).clamp(0.0, PI / 2.0);
+        let t = turbidity;
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let polynomial = |c: [[f64; 4]; 3]| {
+            let cubic = |c: [f64; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+            t * t * cubic(c[0]) + t * cubic(c[1]) + cubic(c[2])
+        };
+        let zenith_x = polynomial([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = polynomial([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let zenith = [zenith_luminance.max(0.0), zenith_x, zenith_y];
+        let zenith = [0, 1, 2].map(|i| zenith[i] / perez_function(perez[i], 1.0, theta_s));
+
+        // sunlight is dimmed and reddened by scattering on the way through the atmosphere
+        let sun_radiance = (elevation > 0.0).then(|| {
+            let air_mass =
+                1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+            let aerosols = 0.04608 * t - 0.04586;
+            let [r, g, b] = SUN_CHANNEL_WAVELENGTHS.map(|lambda: f64| {
+                let rayleigh = 0.008735 * lambda.powf(-4.08);
+                let mie = aerosols * lambda.powf(-1.3);
+                SUN_LUMINANCE * SKY_RADIANCE_SCALE * (-air_mass * (rayleigh + mie)).exp()
+            });
+            Color::new(r, g, b)
+        });
+
+        let mut sky = Self {
+            sun_direction,
+            perez,
+            zenith,
+            sun_radiance,
+            ground_albedo: Color::new(0.3, 0.3, 0.3),
+            ground_irradiance: Color::default(),
+            sun_probability: 0.0,
+            luminance: 0.0,
+        };
+
+        // integrate the sky over the upper hemisphere
+        let (rings, segments) = SKY_INTEGRATION_STEPS;
+        let (d_theta, d_phi) = (PI / 2.0 / rings as f64, 2.0 * PI / segments as f64);
+        let mut sky_irradiance = Color::default();
+        let mut sky_power = 0.0;
+        for ring in 0..rings {
+            let (sin_theta, cos_theta) = ((ring as f64 + 0.5) * d_theta).sin_cos();
+            for segment in 0..segments {
+                let (sin_phi, cos_phi) = ((segment as f64 + 0.5) * d_phi).sin_cos();
+                let direction = Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+                let radiance = sky.sky_radiance(direction) * (sin_theta * d_theta * d_phi);
+                sky_irradiance += radiance * cos_theta;
+                sky_power += radiance.luminance();
+            }
+        }
+
+        let solid_angle = 2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos());
+        sky.ground_irradiance = sky_irradiance;
+        sky.luminance = sky_power;
+        if let Some(sun) = sky.sun_radiance {
+            sky.ground_irradiance += sun * (solid_angle * sun_direction.y());
+            let sun_power = sun.luminance() * solid_angle;
+            sky.sun_probability = (sun_power / (sun_power + sky_power)).min(MAX_SUN_PROBABILITY);
+            sky.luminance += sun_power;
+        }
+
+        sky
+    }
+
+    /// Returns a sky with the sun at the position for a date, time and place, see
+    /// `SunPosition::from_date`.
+    pub fn from_date(month: u32, day: u32, hour: f64, latitude: f64, turbidity: f64) -> Self {
+        let sun = SunPosition::from_date(month, day, hour, latitude);
+        Self::new(sun.elevation, sun.azimuth, turbidity)
+    }
+
+    /// Sets the color of the ground below the horizon (default: `0.3` gray).
+    pub fn ground_albedo(mut self, albedo: Color) -> Self {
+        self.ground_albedo = albedo;
+        self
+    }
+
+    /// Returns the radiance of the sky without the sun, for a direction above the horizon.
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        let cos_theta = direction.y().max(1.0e-3);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * perez_function(self.perez[i], cos_theta, gamma));
+        if y <= 0.0 {
+            return Color::default();
+        }
+
+        let xyz = Vec3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        let rgb = spectrum::colorimetric_xyz_to_linear_srgb(xyz) * SKY_RADIANCE_SCALE;
+        Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+    }
+}
+
+impl Environment for PhysicalSky {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let direction = direction.unit_vector();
+        if direction.y() <= 0.0 {
+            return self.ground_albedo * self.ground_irradiance * (1.0 / PI);
+        }
+
+        let sky = self.sky_radiance(direction);
+        match self.sun_radiance {
+            Some(sun) if direction.dot(self.sun_direction) >= SUN_ANGULAR_RADIUS.cos() => sky + sun,
+            _ => sky,
+        }
+    }
+
+    fn importance_sampled(&self) -> bool {
+        true
+    }
+
+    fn sample_li(&self, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let choice = sampler.get_1d();
+        let u = sampler.get_2d();
+        let wi = match choice < self.sun_probability {
+            true => {
+                let local = sampling::sample_uniform_cone(u, SUN_ANGULAR_RADIUS.cos());
+                Onb::from_w(self.sun_direction).local_to_world(local)
+            }
+            false => sampling::sample_uniform_sphere(u),
+        };
+
+        Some(LightSample {
+            radiance: self.radiance(wi),
+            wi,
+            distance: f64::INFINITY,
+            pdf: self.pdf(wi),
+            delta: false,
+        })
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let cos_radius = SUN_ANGULAR_RADIUS.cos();
+        let sun = match direction.unit_vector().dot(self.sun_direction) >= cos_radius {
+            true => self.sun_probability / (2.0 * PI * (1.0 - cos_radius)),
+            false => 0.0,
+        };
+
+        sun + (1.0 - self.sun_probability) / (4.0 * PI)
+    }
+
+    fn power(&self, scene_radius: f64) -> f64 {
+        // the diffuse ground reflects albedo times irradiance over the lower hemisphere
+        let ground = 2.0 * (self.ground_albedo * self.ground_irradiance).luminance();
+        PI * scene_radius * scene_radius * (self.luminance + ground)
+    }
+}
+
+/// Returns the Perez sky distribution with `coefficients` for a direction with `cos_theta` to the
+/// zenith and an angle `gamma` (in radians) to the sun.
+fn perez_function(coefficients: [f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = coefficients;
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// Position of the sun in the sky.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunPosition {
+    /// Angle above the horizon in degrees
+    pub elevation: f64,
+    /// Angle clockwise from north in degrees
+    pub azimuth: f64,
+}
+
+impl SunPosition {
+    /// Returns the position of the sun on a day of the year at a local solar time, where the sun
+    /// is highest at `12`.
+    ///
+    /// Arguments:
+    ///
+    /// * `month`, `day`: Date, starting at `1`.
+    /// * `hour`: Solar time in hours, e.g. `15.5` for half past three in the afternoon.
+    /// * `latitude`: Latitude in degrees, positive on the northern hemisphere.
+    pub fn from_date(month: u32, day: u32, hour: f64, latitude: f64) -> Self {
+        const DAYS_BEFORE_MONTH: [u32; 12] =
+            [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let month = month.clamp(1, 12) as usize;
+        let day_of_year = (DAYS_BEFORE_MONTH[month - 1] + day) as f64;
+
+        let declination =
+            (-23.44_f64).to_radians() * (2.0 * PI / 365.0 * (day_of_year + 10.0)).cos();
+        let hour_angle = (15.0 * (hour - 12.0)).to_radians();
+        let latitude = latitude.to_radians();
+
+        // direction of the sun in the east, north and up axes of the observer
+        let east = -declination.cos() * hour_angle.sin();
+        let north = latitude.cos() * declination.sin()
+            - latitude.sin() * declination.cos() * hour_angle.cos();
+        let up = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+
+        Self {
+            elevation: up.clamp(-1.0, 1.0).asin().to_degrees(),
+            azimuth: east.atan2(north).to_degrees().rem_euclid(360.0),
+        }
+    }
+}
+
+impl FromStr for SunPosition {
+    type Err = String;
+
+    /// Parses a position written as `elevation,azimuth` (in degrees) or as
+    /// `month-day,hour,latitude`, see `SunPosition::from_date`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(',').map(str::trim).collect();
+        let number = |field: &str| field.parse::<f64>().map_err(|e| e.to_string());
+
+        match fields[..] {
+            [elevation, azimuth] => {
+                let elevation = number(elevation)?;
+                if !(-90.0..=90.0).contains(&elevation) {
+                    return Err("elevation should be between -90 and 90 degrees".to_string());
+                }
+                Ok(Self {
+                    elevation,
+                    azimuth: number(azimuth)?,
+                })
+            }
+            [date, hour, latitude] => {
+                let (month, day) = date
+                    .split_once('-')
+                    .and_then(|(month, day)| Some((month.parse().ok()?, day.parse().ok()?)))
+                    .filter(|&(month, day)| (1..=12).contains(&month) && (1..=31).contains(&day))
+                    .ok_or_else(|| "date should be written as `month-day`".to_string())?;
+                let (hour, latitude) = (number(hour)?, number(latitude)?);
+                if !(0.0..=24.0).contains(&hour) {
+                    return Err("hour should be between 0 and 24".to_string());
+                }
+                if !(-90.0..=90.0).contains(&latitude) {
+                    return Err("latitude should be between -90 and 90 degrees".to_string());
+                }
+                Ok(Self::from_date(month, day, hour, latitude))
+            }
+            _ => Err(
+                "sun position should be written as `elevation,azimuth` or `month-day,hour,latitude`"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sun_angles() {
+        assert_eq!(
+            "30,120".parse(),
+            Ok(SunPosition {
+                elevation: 30.0,
+                azimuth: 120.0
+            })
+        );
+        assert_eq!(
+            " -5 , 270 ".parse(),
+            Ok(SunPosition {
+                elevation: -5.0,
+                azimuth: 270.0
+            })
+        );
+        assert!("95,0".parse::<SunPosition>().is_err());
+        assert!("high,0".parse::<SunPosition>().is_err());
+    }
+
+    #[test]
+    fn parse_sun_date() {
+        // at noon of the summer solstice, the sun is in the zenith at the tropic of cancer and
+        // in the north at the equator
+        let tropic: SunPosition = "6-21,12,23.44".parse().unwrap();
+        assert!((tropic.elevation - 90.0).abs() < 0.1);
+
+        let equator: SunPosition = "6-21,12,0".parse().unwrap();
+        assert!((equator.elevation - 66.56).abs() < 0.1);
+        assert!(equator.azimuth.abs() < 1e-6);
+
+        // the sun rises in the east at the equinox
+        let morning: SunPosition = "3-20,6,45".parse().unwrap();
+        assert!(morning.elevation.abs() < 1.0);
+        assert!((morning.azimuth - 90.0).abs() < 1.0);
+
+        let afternoon: SunPosition = "3-20,15,45".parse().unwrap();
+        assert_eq!(afternoon, SunPosition::from_date(3, 20, 15.0, 45.0));
+        assert!(afternoon.azimuth > 180.0 && afternoon.azimuth < 270.0);
+    }
+
+    #[test]
+    fn parse_invalid_sun_date() {
+        for position in [
+            "13-1,12,0",
+            "6/21,12,0",
+            "6-21,25,0",
+            "6-21,12,91",
+            "1,2,3,4",
+            "",
+        ] {
+            assert!(position.parse::<SunPosition>().is_err(), "{}", position);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use super::{
+    adaptive::PixelStatistics, display::DisplayPipeline, scheduler::Region, spectrum,
+    traits::Filter, Color, Vec3,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Identifies checkpoint files written by `Film::save_checkpoint`.
+const CHECKPOINT_MAGIC: &[u8; 8] = b"RTCKPT04";
+
+/// Color space of the samples that are added to a film.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleSpace {
+    /// Linear sRGB, written as is.
+    Rgb,
+    /// CIE XYZ (of spectral renders), converted to linear sRGB when the image is written.
+    Xyz,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FilmPixel {
+    /// Statistics of the samples taken _in_ this pixel.
+    stats: PixelStatistics,
+    /// Sum of the filter weighted samples that contribute to this pixel.
+    weighted_sum: Vec3,
+    weight_sum: f64,
+}
+
+impl FilmPixel {
+    /// Returns the reconstructed linear sRGB color of the pixel, clamped at zero because filters
+    /// with negative lobes can undershoot (and XYZ colors can be outside of the sRGB gamut).
+    /// Pixels without samples are black.
+    fn color(&self, space: SampleSpace) -> Color {
+        if self.weight_sum <= 0.0 {
+            return Color::default();
+        }
+
+        to_rgb(self.weighted_sum / self.weight_sum, space)
+    }
+}
+
+/// Accumulates the samples of every pixel of the image, so it can be rendered in multiple passes.
+/// Every sample is splatted to the pixels around it, weighted by the reconstruction filter.
+///
+/// Only the pixels within the `region` of the image are written. Samples are taken in the
+/// `sample_region`, which also includes the pixels just outside the `region` that contribute to
+/// it through the filter.
+///
+/// Pixels are addressed with `(i, j)`, where `(0, 0)` is the bottom left corner of the image.
+pub struct Film {
+    width: usize,
+    height: usize,
+    region: Region,
+    sample_region: Region,
+    filter: Box<dyn Filter>,
+    space: SampleSpace,
+    /// Pixels of the `sample_region` (top to bottom, left to right).
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(
+        width: usize,
+        height: usize,
+        region: Region,
+        filter: Box<dyn Filter>,
+        space: SampleSpace,
+    ) -> Self {
+        assert!(
+            region.inside(&Region::full(width, height)),
+            "region is outside the film"
+        );
+
+        // pixels further than the filter radius from the region do not contribute to it
+        let margin = (filter.radius() - 0.5).ceil().max(0.0) as usize;
+        let sample_region = Region::new(
+            region.x0.saturating_sub(margin),
+            region.y0.saturating_sub(margin),
+            (region.x1 + margin).min(width),
+            (region.y1 + margin).min(height),
+        );
+
+        Self {
+            width,
+            height,
+            region,
+            sample_region,
+            filter,
+            space,
+            pixels: vec![FilmPixel::default(); sample_region.area()],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn sample_region(&self) -> Region {
+        self.sample_region
+    }
+
+    pub fn space(&self) -> SampleSpace {
+        self.space
+    }
+
+    /// Returns the index of the pixel at image coordinates `(x, y)`.
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            self.sample_region.contains(x, y),
+            "pixel is outside the film"
+        );
+        (y - self.sample_region.y0) * self.sample_region.width() + (x - self.sample_region.x0)
+    }
+
+    /// Returns the statistics of the samples taken in pixel `(i, j)`.
+    pub fn pixel(&self, i: usize, j: usize) -> &PixelStatistics {
+        &self.pixels[self.index(i, self.height - 1 - j)].stats
+    }
+
+    /// Adds a sample that was taken in pixel `(i, j)`, in the color space of the film.
+    ///
+    /// Arguments:
+    ///
+    /// * `offset`: Position of the sample within the pixel, `(0, 0)` is the bottom left corner and
+    ///   `(1, 1)` the top right corner.
+    pub fn add_sample(&mut self, i: usize, j: usize, offset: (f64, f64), color: Color) {
+        let index = self.index(i, self.height - 1 - j);
+        self.pixels[index]
+            .stats
+            .add(to_rgb(Vec3::from(color), self.space));
+
+        // position relative to the pixel center, in image coordinates
+        let (dx, dy) = (offset.0 - 0.5, 0.5 - offset.1);
+        let radius = self.filter.radius();
+        let (x, y) = (i as isize, (self.height - 1 - j) as isize);
+
+        for oy in (dy - radius).ceil() as isize..=(dy + radius).floor() as isize {
+            for ox in (dx - radius).ceil() as isize..=(dx + radius).floor() as isize {
+                let (px, py) = (x + ox, y + oy);
+                if px < 0 || py < 0 || !self.sample_region.contains(px as usize, py as usize) {
+                    continue;
+                }
+
+                let weight = self.filter.evaluate(dx - ox as f64, dy - oy as f64);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index = self.index(px as usize, py as usize);
+                let pixel = &mut self.pixels[index];
+                pixel.weighted_sum += weight * Vec3::from(color);
+                pixel.weight_sum += weight;
+            }
+        }
+    }
+
+    /// Returns the image coordinates and pixels of the region, in the order they are written to
+    /// an image.
+    fn region_pixels(&self) -> impl Iterator<Item = ((usize, usize), &FilmPixel)> {
+        (self.region.y0..self.region.y1).flat_map(move |y| {
+            (self.region.x0..self.region.x1).map(move |x| ((x, y), &self.pixels[self.index(x, y)]))
+        })
+    }
+
+    /// Returns the number of samples of every pixel in the region, in the order they are written
+    /// to an image.
+    pub fn sample_counts(&self) -> Vec<usize> {
+        self.region_pixels()
+            .map(|(_, pixel)| pixel.stats.count())
+            .collect()
+    }
+
+    /// Writes the reconstructed color of every pixel in the region as PPM image
+    /// (<https://en.wikipedia.org/wiki/Netpbm#PPM_example>), converted for the display by
+    /// `display`. Pixels without samples are black.
+    pub fn write_ppm<W: Write>(&self, writer: &mut W, display: &DisplayPipeline) -> io::Result<()> {
+        writeln!(
+            writer,
+            "P3\n{} {}\n255",
+            self.region.width(),
+            self.region.height()
+        )?;
+
+        for (position, pixel) in self.region_pixels() {
+            writeln!(
+                writer,
+                "{}",
+                pixel.color(self.space).write_color(display, position)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the image to the file at `path`, see `write_ppm`.
+    pub fn save_ppm(&self, path: &Path, display: &DisplayPipeline) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_ppm(&mut writer, display)?;
+        writer.flush()
+    }
+
+    /// Writes the accumulated samples of every pixel to the file at `path`, so a later render can
+    /// continue from them using `load_checkpoint`.
+    pub fn save_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        for dimension in [
+            self.width,
+            self.height,
+            self.region.x0,
+            self.region.y0,
+            self.region.x1,
+            self.region.y1,
+            self.sample_region.area(),
+            self.space as usize,
+        ] {
+            writer.write_all(&(dimension as u64).to_le_bytes())?;
+        }
+        for pixel in &self.pixels {
+            pixel.stats.write_to(&mut writer)?;
+            for value in [
+                pixel.weighted_sum.x(),
+                pixel.weighted_sum.y(),
+                pixel.weighted_sum.z(),
+                pixel.weight_sum,
+            ] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a film written by `save_checkpoint`. The `filter` should be the same filter that was
+    /// used to render the checkpoint.
+    pub fn load_checkpoint(path: &Path, filter: Box<dyn Filter>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let invalid_data = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid_data("file is not a render checkpoint"));
+        }
+
+        let mut buffer = [0; 8];
+        let mut dimensions = [0; 8];
+        for dimension in &mut dimensions {
+            reader.read_exact(&mut buffer)?;
+            *dimension = u64::from_le_bytes(buffer) as usize;
+        }
+
+        let [width, height, x0, y0, x1, y1, sample_area, space] = dimensions;
+        if x0 > x1 || y0 > y1 || x1 > width || y1 > height {
+            return Err(invalid_data("checkpoint has an invalid region"));
+        }
+        let space = match space {
+            0 => SampleSpace::Rgb,
+            1 => SampleSpace::Xyz,
+            _ => return Err(invalid_data("checkpoint has an invalid color space")),
+        };
+
+        let mut film = Self::new(width, height, Region::new(x0, y0, x1, y1), filter, space);
+        if film.sample_region.area() != sample_area {
+            return Err(invalid_data(
+                "checkpoint was rendered with a different filter radius",
+            ));
+        }
+
+        for pixel in &mut film.pixels {
+            pixel.stats = PixelStatistics::read_from(&mut reader)?;
+
+            let mut values = [0.0; 4];
+            for value in &mut values {
+                reader.read_exact(&mut buffer)?;
+                *value = f64::from_le_bytes(buffer);
+            }
+            pixel.weighted_sum = Vec3::new(values[0], values[1], values[2]);
+            pixel.weight_sum = values[3];
+        }
+
+        Ok(film)
+    }
+}
+
+/// Converts a color in `space` to linear sRGB, with negative channels clamped at zero.
+fn to_rgb(color: Vec3, space: SampleSpace) -> Color {
+    let rgb = match space {
+        SampleSpace::Rgb => color,
+        SampleSpace::Xyz => spectrum::xyz_to_linear_srgb(color),
+    };
+    Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::filters::FilterKind;
+
+    /// Returns a path in the temporary directory that is unique to the test `name`.
+    fn temporary_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ray_tracer_{}_{}.ckpt", name, std::process::id()))
+    }
+
+    fn film() -> Film {
+        let mut film = Film::new(
+            8,
+            6,
+            Region::new(2, 1, 6, 5),
+            FilterKind::Tent.build(None),
+            SampleSpace::Rgb,
+        );
+        let region = film.sample_region();
+        for (k, (x, y)) in (region.x0..region.x1)
+            .flat_map(|x| (region.y0..region.y1).map(move |y| (x, y)))
+            .enumerate()
+        {
+            let (i, j) = (x, film.height() - 1 - y);
+            let value = (k % 10) as f64 / 10.0;
+            film.add_sample(i, j, (0.25, 0.75), Color::new(value, 0.5, 1.0 - value));
+            film.add_sample(i, j, (0.6, 0.1), Color::new(0.2, value, 0.3));
+        }
+        film
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let film = film();
+        let path = temporary_path("round_trip");
+        film.save_checkpoint(&path).unwrap();
+        let loaded = Film::load_checkpoint(&path, FilterKind::Tent.build(None));
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!((loaded.width(), loaded.height()), (8, 6));
+        assert_eq!(loaded.region(), film.region());
+        assert_eq!(loaded.sample_region(), film.sample_region());
+        assert_eq!(loaded.space(), SampleSpace::Rgb);
+        for (pixel, loaded) in film.pixels.iter().zip(&loaded.pixels) {
+            assert_eq!(pixel.stats.count(), loaded.stats.count());
+            assert_eq!(pixel.stats.mean(), loaded.stats.mean());
+            assert_eq!(pixel.stats.variance(), loaded.stats.variance());
+            assert_eq!(pixel.weighted_sum, loaded.weighted_sum);
+            assert_eq!(pixel.weight_sum, loaded.weight_sum);
+        }
+    }
+
+    #[test]
+    fn checkpoint_keeps_sample_space() {
+        let film = Film::new(
+            4,
+            4,
+            Region::full(4, 4),
+            FilterKind::Box.build(None),
+            SampleSpace::Xyz,
+        );
+        let path = temporary_path("sample_space");
+        film.save_checkpoint(&path).unwrap();
+        let loaded = Film::load_checkpoint(&path, FilterKind::Box.build(None));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().space(), SampleSpace::Xyz);
+    }
+
+    #[test]
+    fn truncated_checkpoint_is_rejected() {
+        let path = temporary_path("truncated");
+        film().save_checkpoint(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 8]).unwrap();
+        let loaded = Film::load_checkpoint(&path, FilterKind::Tent.build(None));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn checkpoint_with_other_filter_radius_is_rejected() {
+        let path = temporary_path("filter_radius");
+        film().save_checkpoint(&path).unwrap();
+        let loaded = Film::load_checkpoint(&path, FilterKind::Lanczos.build(Some(3.0)));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let path = temporary_path("not_a_checkpoint");
+        std::fs::write(&path, b"P3\n8 6\n255\n").unwrap();
+        let loaded = Film::load_checkpoint(&path, FilterKind::Box.build(None));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
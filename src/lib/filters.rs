@@ -0,0 +1,243 @@
+use super::traits::Filter;
+use std::{f64::consts::PI, str::FromStr};
+
+/// Gives every sample within the radius the same weight. With a radius of `0.5` every sample
+/// only contributes to the pixel it was taken in.
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        // half open, so a sample on the border of two pixels only counts for one of them
+        let support = -self.radius..self.radius;
+        match support.contains(&x) && support.contains(&y) {
+            true => 1.0,
+            false => 0.0,
+        }
+    }
+}
+
+/// Weight falls off linearly from the pixel center to the radius (triangle filter).
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+/// Gaussian with a standard deviation of a third of the radius, shifted down so it reaches zero
+/// at the radius.
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64) -> Self {
+        Self {
+            radius,
+            sigma: radius / 3.0,
+        }
+    }
+
+    fn gaussian_1d(&self, x: f64) -> f64 {
+        let g = |x: f64| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian_1d(x) * self.gaussian_1d(y)
+    }
+}
+
+/// Cubic filter of Mitchell and Netravali, "Reconstruction Filters in Computer Graphics", with the
+/// recommended `B = C = 1/3`. It has small negative lobes which sharpen the image.
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64) -> Self {
+        Self {
+            radius,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    /// Evaluates the filter for `x` scaled to the `[-2, 2]` support of the cubic.
+    fn mitchell_1d(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+
+        if x <= 1.0 {
+            ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                + (6.0 - 2.0 * b))
+                / 6.0
+        } else if x <= 2.0 {
+            ((-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell_1d(2.0 * x / self.radius) * self.mitchell_1d(2.0 * y / self.radius)
+    }
+}
+
+/// Sinc filter windowed by a wider sinc that reaches zero at the radius
+/// (<https://en.wikipedia.org/wiki/Lanczos_resampling>). Keeps the most detail, but causes
+/// ringing around sharp edges.
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    fn lanczos_1d(&self, x: f64) -> f64 {
+        if x.abs() >= self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos_1d(x) * self.lanczos_1d(y)
+    }
+}
+
+/// Normalized sinc function.
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1.0e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+/// The available reconstruction filters, parsed from their lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    /// Returns the radius used when no radius is given.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Gaussian => 1.5,
+            Self::Mitchell => 2.0,
+            Self::Lanczos => 2.0,
+        }
+    }
+
+    pub fn build(&self, radius: Option<f64>) -> Box<dyn Filter> {
+        let radius = radius.unwrap_or_else(|| self.default_radius());
+        match self {
+            Self::Box => Box::new(BoxFilter::new(radius)),
+            Self::Tent => Box::new(TentFilter::new(radius)),
+            Self::Gaussian => Box::new(GaussianFilter::new(radius)),
+            Self::Mitchell => Box::new(MitchellFilter::new(radius)),
+            Self::Lanczos => Box::new(LanczosFilter::new(radius)),
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(Self::Box),
+            "tent" => Ok(Self::Tent),
+            "gaussian" => Ok(Self::Gaussian),
+            "mitchell" => Ok(Self::Mitchell),
+            "lanczos" => Ok(Self::Lanczos),
+            _ => Err(format!(
+                "unknown filter `{}` (expected `box`, `tent`, `gaussian`, `mitchell` or `lanczos`)",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filter_kind() {
+        for (name, kind) in [
+            ("box", FilterKind::Box),
+            ("tent", FilterKind::Tent),
+            ("gaussian", FilterKind::Gaussian),
+            ("mitchell", FilterKind::Mitchell),
+            ("lanczos", FilterKind::Lanczos),
+        ] {
+            assert_eq!(name.parse(), Ok(kind));
+            assert_eq!(kind.build(None).radius(), kind.default_radius());
+            assert_eq!(kind.build(Some(3.0)).radius(), 3.0);
+        }
+
+        assert!("Box".parse::<FilterKind>().is_err());
+        assert!("".parse::<FilterKind>().is_err());
+    }
+}
@@ -1,29 +1,80 @@
-use super::{ray::Ray, traits::Material, vec3::Point3, vec3::Vec3};
+use super::{onb::Onb, ray::Ray, traits::Material, vec3::Point3, vec3::Vec3};
 use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct HitRecord {
     p: Point3,
+    /// Shading normal, on the side of the incoming ray
     normal: Vec3,
+    /// Normal of the primitive, on the side of the incoming ray
+    geometric_normal: Vec3,
+    /// Derivatives of the hit point along the surface coordinates `u` and `v`
+    dpdu: Vec3,
+    dpdv: Vec3,
     mat: Rc<dyn Material>,
     t: f64,
+    /// Surface coordinates of the hit point, used to look up textures.
+    uv: (f64, f64),
     front_face: bool,
+    /// Index of the light in the `LightList` and of its primitive that was hit, for surfaces
+    /// that are sampled as lights as well
+    light: Option<(usize, usize)>,
 }
 
 impl HitRecord {
-    pub fn new(p: Point3, r: &Ray, outward_normal: Vec3, mat: Rc<dyn Material>, t: f64) -> Self {
+    pub fn new(
+        p: Point3,
+        r: &Ray,
+        outward_normal: Vec3,
+        mat: Rc<dyn Material>,
+        t: f64,
+        uv: (f64, f64),
+    ) -> Self {
         let front_face = (-r.direction().unit_vector()).dot(outward_normal.unit_vector()) > 0.0;
         let normal = if front_face {
             outward_normal
         } else {
             -outward_normal
         };
+        let onb = Onb::from_w(outward_normal);
 
         Self {
             p,
             normal,
+            geometric_normal: normal,
+            dpdu: onb.u(),
+            dpdv: onb.v(),
             mat,
             t,
+            uv,
             front_face,
+            light: None,
+        }
+    }
+
+    /// Sets the derivatives of the hit point along the surface coordinates, which span the
+    /// tangent plane used by normal and bump maps. Without them an arbitrary frame around the
+    /// normal is used.
+    pub fn tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Marks the hit as one on the primitive `primitive` of the light `light` of the
+    /// `LightList`, so the density of sampling the hit point from the lights can be looked up.
+    pub fn light(mut self, light: usize, primitive: usize) -> Self {
+        self.light = Some((light, primitive));
+        self
+    }
+
+    /// Returns a copy of the record with the shading normal replaced by `outward_normal`, which
+    /// points to the outside of the object like the normal passed to `new`.
+    pub fn with_shading_normal(&self, outward_normal: Vec3) -> Self {
+        let normal = outward_normal.unit_vector();
+        Self {
+            normal: if self.front_face { normal } else { -normal },
+            ..self.clone()
         }
     }
 
@@ -35,14 +86,44 @@ impl HitRecord {
         self.normal
     }
 
+    pub fn geometric_normal(&self) -> Vec3 {
+        self.geometric_normal
+    }
+
+    /// Returns the outward normal of the primitive, independent of the side that was hit.
+    pub fn outward_normal(&self) -> Vec3 {
+        match self.front_face {
+            true => self.geometric_normal,
+            false => -self.geometric_normal,
+        }
+    }
+
+    pub fn dpdu(&self) -> Vec3 {
+        self.dpdu
+    }
+
+    pub fn dpdv(&self) -> Vec3 {
+        self.dpdv
+    }
+
     pub fn get_t(&self) -> f64 {
         self.t
     }
 
+    pub fn uv(&self) -> (f64, f64) {
+        self.uv
+    }
+
     pub fn hit_frontface(&self) -> bool {
         self.front_face
     }
 
+    /// Returns the index of the light and of its primitive that was hit, if the surface is
+    /// sampled as a light.
+    pub fn emitter(&self) -> Option<(usize, usize)> {
+        self.light
+    }
+
     pub fn mat(&self) -> Rc<dyn Material> {
         self.mat.clone()
     }
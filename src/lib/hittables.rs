@@ -1,8 +1,8 @@
 use super::{
-    traits::{Hittable, Material},
-    HitRecord, Point3, Ray, Vec3,
+    traits::{FloatTexture, Hittable, Material},
+    utils, HitRecord, Point3, Ray, Vec3,
 };
-use std::rc::Rc;
+use std::{f64::consts::PI, rc::Rc};
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -19,6 +19,29 @@ impl Sphere {
             mat,
         }
     }
+
+    /// Returns the surface coordinates of the point with normal `n`, where `u` goes around the
+    /// `y` axis (starting at `-x`) and `v` from the bottom (`-y`) to the top of the sphere.
+    fn uv(n: Vec3) -> (f64, f64) {
+        let theta = (-n.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-n.z()).atan2(n.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Returns the derivatives of the surface point along `u` and `v` (see `uv`) at the point
+    /// with normal `n`. They are `None` at the poles, where `u` is undefined.
+    fn tangents(&self, n: Vec3) -> Option<(Vec3, Vec3)> {
+        let (x, y, z) = (n.x(), n.y(), n.z());
+        let rho = (x * x + z * z).sqrt();
+        if rho < 1.0e-9 {
+            return None;
+        }
+
+        let dpdu = 2.0 * PI * self.radius * Vec3::new(z, 0.0, -x);
+        let dpdv = PI * self.radius * Vec3::new(-x * y / rho, rho, -y * z / rho);
+        Some((dpdu, dpdv))
+    }
 }
 
 impl Hittable for Sphere {
@@ -45,13 +68,281 @@ impl Hittable for Sphere {
 
         // get point of inpact
         let p = r.at(root);
-        Some(HitRecord::new(
+        let outward_normal = (p - self.center) / self.radius;
+        let hitrecord = HitRecord::new(
             p,
             r,
-            (p - self.center) / self.radius,
+            outward_normal,
             self.mat.clone(),
             root,
-        ))
+            Self::uv(outward_normal),
+        );
+
+        Some(match self.tangents(outward_normal) {
+            Some((dpdu, dpdv)) => hitrecord.tangents(dpdu, dpdv),
+            None => hitrecord,
+        })
+    }
+}
+
+/// Triangles that share their vertices, e.g. the faces of a model or the two halves of a
+/// `quad`. The front of a triangle is the side from which its vertices appear counterclockwise.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    /// Vertex indices of every triangle
+    indices: Vec<[usize; 3]>,
+    /// Surface coordinates of every vertex
+    uvs: Option<Vec<(f64, f64)>>,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Point3>, indices: Vec<[usize; 3]>) -> Self {
+        assert!(
+            indices.iter().flatten().all(|&i| i < positions.len()),
+            "vertex index out of range"
+        );
+        Self {
+            positions,
+            indices,
+            uvs: None,
+        }
+    }
+
+    /// Returns a parallelogram with a corner at `corner` and the edges `u` and `v`, with surface
+    /// coordinates from `0` to `1` along the edges. The front faces along `u × v`.
+    pub fn quad(corner: Point3, u: Vec3, v: Vec3) -> Self {
+        Self::new(
+            vec![corner, corner + u, corner + u + v, corner + v],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .uvs(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)])
+    }
+
+    /// Sets the surface coordinates of the vertices, used to look up textures. Without them the
+    /// vertices of every triangle have the coordinates `(0, 0)`, `(1, 0)` and `(1, 1)`.
+    pub fn uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "every vertex needs surface coordinates"
+        );
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Returns the number of triangles.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the vertex positions of the triangle `index`.
+    pub fn vertices(&self, index: usize) -> [Point3; 3] {
+        self.indices[index].map(|i| self.positions[i])
+    }
+
+    /// Returns the surface coordinates of the vertices of the triangle `index`.
+    fn vertex_uvs(&self, index: usize) -> [(f64, f64); 3] {
+        match &self.uvs {
+            Some(uvs) => self.indices[index].map(|i| uvs[i]),
+            None => [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+        }
+    }
+
+    /// Returns the surface coordinates at the barycentric coordinates `b` of the triangle
+    /// `index`.
+    pub fn uv(&self, index: usize, b: [f64; 3]) -> (f64, f64) {
+        let [uv0, uv1, uv2] = self.vertex_uvs(index);
+        (
+            b[0] * uv0.0 + b[1] * uv1.0 + b[2] * uv2.0,
+            b[0] * uv0.1 + b[1] * uv1.1 + b[2] * uv2.1,
+        )
+    }
+
+    /// Returns the point at the barycentric coordinates `b` of the triangle `index`.
+    pub fn point(&self, index: usize, b: [f64; 3]) -> Point3 {
+        let [p0, p1, p2] = self.vertices(index);
+        b[0] * p0 + b[1] * p1 + b[2] * p2
+    }
+
+    /// Returns the unit normal on the front of the triangle `index`.
+    pub fn normal(&self, index: usize) -> Vec3 {
+        let [p0, p1, p2] = self.vertices(index);
+        (p1 - p0).cross(p2 - p0).unit_vector()
+    }
+
+    pub fn area(&self, index: usize) -> f64 {
+        let [p0, p1, p2] = self.vertices(index);
+        0.5 * (p1 - p0).cross(p2 - p0).length()
+    }
+
+    /// Intersects `r` with the triangle `index` (Möller-Trumbore). Returns the ray parameter and
+    /// the barycentric coordinates of the hit point.
+    pub fn intersect(
+        &self,
+        index: usize,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, [f64; 3])> {
+        let [p0, p1, p2] = self.vertices(index);
+        let (edge1, edge2) = (p1 - p0, p2 - p0);
+
+        let pvec = r.direction().cross(edge2);
+        let det = edge1.dot(pvec);
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+
+        let tvec = r.origin() - p0;
+        let b1 = tvec.dot(pvec) / det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(edge1);
+        let b2 = r.direction().dot(qvec) / det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(qvec) / det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        Some((t, [1.0 - b1 - b2, b1, b2]))
+    }
+
+    /// Returns the derivatives of the surface point along `u` and `v` on the triangle `index`,
+    /// or `None` if its surface coordinates are degenerate.
+    fn tangents(&self, index: usize) -> Option<(Vec3, Vec3)> {
+        let [p0, p1, p2] = self.vertices(index);
+        let [uv0, uv1, uv2] = self.vertex_uvs(index);
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+
+        let dpdu = (dv12 * dp02 - dv02 * dp12) / det;
+        let dpdv = (du02 * dp12 - du12 * dp02) / det;
+        Some((dpdu, dpdv))
+    }
+}
+
+/// Renders all triangles of a `TriangleMesh` with one material.
+pub struct Mesh {
+    mesh: Rc<TriangleMesh>,
+    mat: Rc<dyn Material>,
+    /// Index of the `AreaLight` of the mesh in the `LightList`
+    light: Option<usize>,
+}
+
+impl Mesh {
+    pub fn new(mesh: Rc<TriangleMesh>, mat: Rc<dyn Material>) -> Self {
+        Self {
+            mesh,
+            mat,
+            light: None,
+        }
+    }
+
+    /// Marks the mesh as the surface of the `AreaLight` at index `light` of the `LightList`, as
+    /// returned by `LightList::add`.
+    pub fn light(mut self, light: usize) -> Self {
+        self.light = Some(light);
+        self
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // track the closest triangle
+        let mut closest = None;
+        let mut t_max = t_max;
+        for index in 0..self.mesh.len() {
+            if let Some((t, b)) = self.mesh.intersect(index, r, t_min, t_max) {
+                t_max = t;
+                closest = Some((index, t, b));
+            }
+        }
+
+        let (index, t, b) = closest?;
+        let hitrecord = HitRecord::new(
+            self.mesh.point(index, b),
+            r,
+            self.mesh.normal(index),
+            self.mat.clone(),
+            t,
+            self.mesh.uv(index, b),
+        );
+
+        let hitrecord = match self.mesh.tangents(index) {
+            Some((dpdu, dpdv)) => hitrecord.tangents(dpdu, dpdv),
+            None => hitrecord,
+        };
+        Some(match self.light {
+            Some(light) => hitrecord.light(light, index),
+            None => hitrecord,
+        })
+    }
+}
+
+/// Cuts holes into an object with an opacity texture, e.g. for leaves or fences. A hit is kept
+/// with probability equal to the opacity (`1` is solid, `0` is a hole), otherwise the ray
+/// continues to the next surface without being bent or attenuated. Partial opacity averages to
+/// a semi-transparent surface over many samples.
+///
+/// The test is decided by a hash of the ray, so it is random across rays but a given ray always
+/// sees the same surfaces.
+pub struct AlphaMask {
+    object: Box<dyn Hittable>,
+    opacity: Rc<dyn FloatTexture>,
+}
+
+impl AlphaMask {
+    pub fn new(object: Box<dyn Hittable>, opacity: Rc<dyn FloatTexture>) -> Self {
+        Self { object, opacity }
+    }
+}
+
+impl Hittable for AlphaMask {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_min = t_min;
+
+        loop {
+            let hitrecord = self.object.hit(r, t_min, t_max)?;
+            let opacity = self
+                .opacity
+                .value(hitrecord.uv(), hitrecord.get_inpact_point());
+            if opacity >= 1.0 {
+                return Some(hitrecord);
+            }
+
+            let (o, d) = (r.origin(), r.direction());
+            let u = utils::hash_to_double(utils::hash(&[
+                o.x().to_bits(),
+                o.y().to_bits(),
+                o.z().to_bits(),
+                d.x().to_bits(),
+                d.y().to_bits(),
+                d.z().to_bits(),
+                hitrecord.get_t().to_bits(),
+            ]));
+            if u < opacity {
+                return Some(hitrecord);
+            }
+
+            // look for the next surface behind the cut out one
+            t_min = hitrecord.get_t().next_up();
+        }
     }
 }
 
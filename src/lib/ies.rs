@@ -0,0 +1,235 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// Distribution of the luminous intensity of a light fixture, read from an IES photometric file
+/// (ANSI/IESNA LM-63, <https://www.ies.org/standards/standards-for-photometry/>).
+///
+/// Only type C photometry, which is used by nearly all architectural fixtures, is supported. A
+/// direction is described by its vertical angle from the nadir (`0`, straight down) to the zenith
+/// (`180`), and its horizontal angle around the vertical axis, starting at the `C0` plane.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    /// Vertical angles in ascending order in degrees
+    vertical_angles: Vec<f64>,
+    /// Horizontal angles in ascending order in degrees
+    horizontal_angles: Vec<f64>,
+    /// Intensity in candela for every horizontal angle (rows) and vertical angle (columns)
+    candela: Vec<f64>,
+}
+
+impl IesProfile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::parse(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Parses the contents of an IES file in the LM-63-1986, -1991, -1995 or -2002 format.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid_data = |message: &str| io::Error::new(ErrorKind::InvalidData, message);
+
+        // keywords and comments are followed by the line with the lamp tilt
+        let mut lines = text.lines();
+        let tilt = loop {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid_data("IES file has no TILT line"))?;
+            if let Some(tilt) = line.trim_start().strip_prefix("TILT=") {
+                break tilt.trim();
+            }
+        };
+
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>());
+        let mut next = |count: usize| -> io::Result<Vec<f64>> {
+            (0..count)
+                .map(|_| match numbers.next() {
+                    Some(Ok(number)) => Ok(number),
+                    Some(Err(_)) => Err(invalid_data("invalid number in IES file")),
+                    None => Err(invalid_data("IES file is truncated")),
+                })
+                .collect()
+        };
+
+        // the tilt of the lamp only changes the output slightly and is ignored
+        if tilt == "INCLUDE" {
+            let header = next(2)?;
+            let pairs = header[1] as usize;
+            next(2 * pairs)?;
+        }
+
+        let header = next(13)?;
+        let multiplier = header[2];
+        let vertical_count = header[3] as usize;
+        let horizontal_count = header[4] as usize;
+        let photometric_type = header[5] as usize;
+        let ballast_factor = header[10];
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid_data("IES file has no angles"));
+        }
+        if photometric_type != 1 {
+            return Err(invalid_data(
+                "only type C photometry is supported in IES files",
+            ));
+        }
+
+        let vertical_angles = next(vertical_count)?;
+        let horizontal_angles = next(horizontal_count)?;
+        let ascending = |angles: &[f64]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending(&vertical_angles) || !ascending(&horizontal_angles) {
+            return Err(invalid_data("angles in IES file are not ascending"));
+        }
+
+        let candela = next(vertical_count * horizontal_count)?
+            .into_iter()
+            .map(|value| (value * multiplier * ballast_factor).max(0.0))
+            .collect();
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        })
+    }
+
+    /// Returns the largest intensity of the profile in candela.
+    pub fn max_candela(&self) -> f64 {
+        self.candela.iter().fold(0.0, |max, &value| max.max(value))
+    }
+
+    /// Returns the intensity in candela at the `vertical` and `horizontal` angle in degrees,
+    /// interpolated bilinearly between the measured angles. The horizontal angle is mirrored
+    /// into the measured range for profiles that only store a symmetric part.
+    pub fn candela(&self, vertical: f64, horizontal: f64) -> f64 {
+        let (first, last) = (
+            self.vertical_angles[0],
+            self.vertical_angles.last().unwrap(),
+        );
+        if vertical < first || vertical > *last {
+            return 0.0;
+        }
+
+        // profiles with a single horizontal angle are rotationally symmetric, ones from 90 to 270
+        // degrees symmetric about the 90-270 plane, ones up to 180 degrees symmetric about the
+        // 0-180 plane and ones up to 90 degrees in each quadrant
+        let (start, symmetry) = (
+            self.horizontal_angles[0],
+            *self.horizontal_angles.last().unwrap(),
+        );
+        let mut horizontal = horizontal.rem_euclid(360.0);
+        if start == 90.0 && symmetry == 270.0 {
+            if !(90.0..=270.0).contains(&horizontal) {
+                horizontal = (180.0 - horizontal).rem_euclid(360.0);
+            }
+        } else {
+            if symmetry <= 180.0 && horizontal > 180.0 {
+                horizontal = 360.0 - horizontal;
+            }
+            if symmetry <= 90.0 && horizontal > 90.0 {
+                horizontal = 180.0 - horizontal;
+            }
+        }
+
+        let (v0, v1, tv) = interpolation(&self.vertical_angles, vertical);
+        let (h0, h1, th) = interpolation(&self.horizontal_angles, horizontal);
+
+        let value = |h: usize, v: usize| self.candela[h * self.vertical_angles.len() + v];
+        let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+        lerp(
+            lerp(value(h0, v0), value(h0, v1), tv),
+            lerp(value(h1, v0), value(h1, v1), tv),
+            th,
+        )
+    }
+}
+
+/// Returns the indices of the angles around `angle` and the position of `angle` between them.
+/// Angles outside of the range are clamped to the closest end.
+fn interpolation(angles: &[f64], angle: f64) -> (usize, usize, f64) {
+    if angles.len() == 1 {
+        return (0, 0, 0.0);
+    }
+
+    let upper = angles
+        .partition_point(|&a| a <= angle)
+        .clamp(1, angles.len() - 1);
+    let lower = upper - 1;
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    (lower, upper, t.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Profile with a multiplier of 2 that is symmetric in each quadrant.
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] quadrant symmetric fixture
+[MANUFAC] none
+TILT=NONE
+1 1000 2 3 2 1 1 0 0 0
+1 1 10
+0 45 90
+0 90
+100 50 0
+80 40 0
+";
+
+    #[test]
+    fn parse_and_interpolate() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+
+        assert_eq!(profile.max_candela(), 200.0);
+        assert_eq!(profile.candela(0.0, 0.0), 200.0);
+        assert_eq!(profile.candela(45.0, 90.0), 80.0);
+        assert_eq!(profile.candela(22.5, 45.0), 135.0);
+        assert_eq!(profile.candela(120.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn horizontal_angles_are_mirrored() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+
+        assert_eq!(profile.candela(45.0, 180.0), profile.candela(45.0, 0.0));
+        assert_eq!(profile.candela(45.0, 270.0), profile.candela(45.0, 90.0));
+        assert_eq!(profile.candela(30.0, 300.0), profile.candela(30.0, 60.0));
+        assert_eq!(profile.candela(30.0, -30.0), profile.candela(30.0, 30.0));
+    }
+
+    #[test]
+    fn profiles_from_90_to_270_degrees_are_mirrored() {
+        let text = PROFILE
+            .replace("3 2 1 1", "3 3 1 1")
+            .replace("0 90\n", "90 180 270\n")
+            .replace("80 40 0\n", "80 40 0\n60 30 0\n");
+        let profile = IesProfile::parse(&text).unwrap();
+
+        assert_eq!(profile.candela(45.0, 90.0), 100.0);
+        assert_eq!(profile.candela(45.0, 270.0), 60.0);
+        assert_eq!(profile.candela(45.0, 0.0), profile.candela(45.0, 180.0));
+        assert_eq!(profile.candela(45.0, 60.0), profile.candela(45.0, 120.0));
+        assert_eq!(profile.candela(45.0, 300.0), profile.candela(45.0, 240.0));
+        assert_eq!(profile.candela(45.0, -30.0), profile.candela(45.0, 210.0));
+    }
+
+    #[test]
+    fn tilt_values_are_skipped() {
+        let text = PROFILE.replace("TILT=NONE", "TILT=INCLUDE\n1\n3\n0 45 90\n1 0.9 0.8");
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.max_candela(), 200.0);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert!(IesProfile::parse("IESNA:LM-63-2002\n1 2 3").is_err());
+        assert!(IesProfile::parse(&PROFILE.replace("3 2 1 1", "3 2 2 1")).is_err());
+        assert!(IesProfile::parse(&PROFILE.replace("0 45 90", "0 90 45")).is_err());
+        assert!(IesProfile::parse(PROFILE.trim_end_matches("80 40 0\n")).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use super::{display::TransferFunction, Color};
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+/// Grid of linear colors, e.g. loaded from a file to be used as texture.
+///
+/// Pixels are addressed with `(x, y)`, where `(0, 0)` is the top left corner of the image.
+pub struct Image {
+    width: usize,
+    height: usize,
+    /// Pixels of the image (top to bottom, left to right).
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "image should not be empty");
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "number of pixels does not match the size"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Reads a binary (`P6`) or plain (`P3`) PPM image
+    /// (<https://netpbm.sourceforge.net/doc/ppm.html>), with 8 or 16 bits per channel.
+    ///
+    /// Arguments:
+    ///
+    /// * `transfer`: Encoding of the stored values, e.g. `Srgb` for color images and `Linear` for
+    ///   images holding data such as roughness.
+    pub fn read_ppm(path: &Path, transfer: TransferFunction) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid_data = |message: &str| io::Error::new(ErrorKind::InvalidData, message);
+
+        let mut position = 0;
+        let magic = next_token(&bytes, &mut position).ok_or_else(|| invalid_data("empty image"))?;
+        let mut header = [0; 3];
+        for value in &mut header {
+            *value = next_token(&bytes, &mut position)
+                .and_then(|token| std::str::from_utf8(token).ok()?.parse().ok())
+                .ok_or_else(|| invalid_data("invalid PPM header"))?;
+        }
+
+        let [width, height, max_value] = header;
+        if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+            return Err(invalid_data("invalid PPM header"));
+        }
+
+        let count = 3 * width * height;
+        let values: Vec<usize> = match magic {
+            b"P3" => (0..count)
+                .map(|_| {
+                    next_token(&bytes, &mut position)
+                        .and_then(|token| std::str::from_utf8(token).ok()?.parse().ok())
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| invalid_data("PPM image is truncated"))?,
+            b"P6" => {
+                // a single whitespace separates the header from the binary data
+                let data = bytes
+                    .get(position + 1..)
+                    .ok_or_else(|| invalid_data("PPM image is truncated"))?;
+                let bytes_per_value = if max_value < 256 { 1 } else { 2 };
+                if data.len() < count * bytes_per_value {
+                    return Err(invalid_data("PPM image is truncated"));
+                }
+
+                data.chunks_exact(bytes_per_value)
+                    .take(count)
+                    .map(|chunk| {
+                        chunk
+                            .iter()
+                            .fold(0, |value, &byte| value << 8 | byte as usize)
+                    })
+                    .collect()
+            }
+            _ => return Err(invalid_data("file is not a PPM image")),
+        };
+
+        let decode =
+            |value: usize| transfer.decode((value.min(max_value)) as f64 / max_value as f64);
+        let pixels = values
+            .chunks_exact(3)
+            .map(|rgb| Color::new(decode(rgb[0]), decode(rgb[1]), decode(rgb[2])))
+            .collect();
+
+        Ok(Self::new(width, height, pixels))
+    }
+
+    /// Reads a Radiance HDR image (`.hdr`, <https://radsite.lbl.gov/radiance/refer/filefmts.pdf>)
+    /// with run length encoded or flat RGBE pixels. The values are linear radiance and not
+    /// limited to `[0, 1]`.
+    pub fn read_hdr(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid_data = |message: &str| io::Error::new(ErrorKind::InvalidData, message);
+
+        // the header consists of lines up to an empty line, followed by the resolution
+        let mut lines = bytes.split(|&byte| byte == b'\n');
+        let mut position = 0;
+        let mut next_line = || {
+            let line = lines.next()?;
+            position += line.len() + 1;
+            Some(line)
+        };
+
+        let magic = next_line().ok_or_else(|| invalid_data("empty image"))?;
+        if !magic.starts_with(b"#?") {
+            return Err(invalid_data("file is not a Radiance HDR image"));
+        }
+        loop {
+            let line = next_line().ok_or_else(|| invalid_data("HDR header is truncated"))?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid_data("only RGBE HDR images are supported"));
+            }
+        }
+
+        let resolution = next_line().ok_or_else(|| invalid_data("HDR header is truncated"))?;
+        let resolution =
+            std::str::from_utf8(resolution).map_err(|_| invalid_data("invalid HDR resolution"))?;
+        let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (
+                width
+                    .parse()
+                    .map_err(|_| invalid_data("invalid HDR resolution"))?,
+                height
+                    .parse()
+                    .map_err(|_| invalid_data("invalid HDR resolution"))?,
+            ),
+            _ => {
+                return Err(invalid_data(
+                    "only `-Y <height> +X <width>` HDR images are supported",
+                ))
+            }
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid_data("invalid HDR resolution"));
+        }
+
+        let data = bytes.get(position..).unwrap_or_default();
+        let mut reader = RgbeReader { data, position: 0 };
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0_u8; 4]; width];
+        for _ in 0..height {
+            reader
+                .read_scanline(&mut scanline)
+                .ok_or_else(|| invalid_data("HDR image is truncated"))?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+        }
+
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns the bilinearly interpolated color at texture coordinates `uv`, where `(0, 0)` is
+    /// the bottom left and `(1, 1)` the top right corner. The image repeats outside of `[0, 1]`.
+    pub fn lookup(&self, uv: (f64, f64)) -> Color {
+        // continuous pixel coordinates, with pixel centers at integer positions
+        let x = uv.0 * self.width as f64 - 0.5;
+        let y = (1.0 - uv.1) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+
+        let wrap = |value: f64, size: usize| value.rem_euclid(size as f64) as usize % size;
+        let texel =
+            |ox: f64, oy: f64| self.pixel(wrap(x0 + ox, self.width), wrap(y0 + oy, self.height));
+
+        (1.0 - dy) * ((1.0 - dx) * texel(0.0, 0.0) + dx * texel(1.0, 0.0))
+            + dy * ((1.0 - dx) * texel(0.0, 1.0) + dx * texel(1.0, 1.0))
+    }
+}
+
+/// Reads the RGBE pixels of a Radiance HDR image.
+struct RgbeReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl RgbeReader<'_> {
+    fn next(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    /// Reads the next scanline into `scanline`, or returns `None` if the data ends early.
+    fn read_scanline(&mut self, scanline: &mut [[u8; 4]]) -> Option<()> {
+        let width = scanline.len();
+        let header = self.data.get(self.position..self.position + 4)?;
+        let run_length_encoded = (8..0x8000).contains(&width)
+            && header[0] == 2
+            && header[1] == 2
+            && (usize::from(header[2]) << 8 | usize::from(header[3])) == width;
+
+        if !run_length_encoded {
+            return self.read_flat_scanline(scanline);
+        }
+
+        // every channel is encoded separately as runs and literal sequences
+        self.position += 4;
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.next()?;
+                if count > 128 {
+                    let count = usize::from(count - 128);
+                    let value = self.next()?;
+                    for pixel in scanline.get_mut(x..x + count)? {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                } else {
+                    let count = usize::from(count);
+                    if count == 0 {
+                        return None;
+                    }
+                    for pixel in scanline.get_mut(x..x + count)? {
+                        pixel[channel] = self.next()?;
+                    }
+                    x += count;
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// Reads a scanline of plain pixels, where `(1, 1, 1, n)` repeats the previous pixel (old run
+    /// length encoding).
+    fn read_flat_scanline(&mut self, scanline: &mut [[u8; 4]]) -> Option<()> {
+        let mut x = 0;
+        let mut shift = 0;
+        while x < scanline.len() {
+            let pixel = [self.next()?, self.next()?, self.next()?, self.next()?];
+            if pixel[..3] == [1, 1, 1] && x > 0 {
+                let count = usize::from(pixel[3]) << shift;
+                let previous = scanline[x - 1];
+                scanline.get_mut(x..x + count)?.fill(previous);
+                x += count;
+                shift += 8;
+            } else {
+                scanline[x] = pixel;
+                x += 1;
+                shift = 0;
+            }
+        }
+
+        Some(())
+    }
+}
+
+/// Converts a pixel with a shared exponent to a color.
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::default();
+    }
+
+    let scale = 2_f64.powi(i32::from(rgbe[3]) - 136);
+    Color::new(
+        (f64::from(rgbe[0]) + 0.5) * scale,
+        (f64::from(rgbe[1]) + 0.5) * scale,
+        (f64::from(rgbe[2]) + 0.5) * scale,
+    )
+}
+
+/// Returns the next whitespace separated token of a PPM header, skipping `#` comments.
+fn next_token<'a>(bytes: &'a [u8], position: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        match bytes.get(*position)? {
+            b'#' => {
+                while *bytes.get(*position)? != b'\n' {
+                    *position += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *position += 1,
+            _ => break,
+        }
+    }
+
+    let start = *position;
+    while bytes
+        .get(*position)
+        .is_some_and(|byte| !byte.is_ascii_whitespace())
+    {
+        *position += 1;
+    }
+
+    Some(&bytes[start..*position])
+}
@@ -0,0 +1,644 @@
+use super::{sampling::Distribution1D, Point3, Vec3};
+use std::{f64::consts::PI, str::FromStr};
+
+/// Extent, power and emission directions of a light, used to estimate how much it contributes
+/// at a point when choosing a light to sample.
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    /// Corners of the box that contains the light
+    min: Point3,
+    max: Point3,
+    /// Power of the light. For lights that are brighter in some directions, it is the power the
+    /// light would have if it emitted its largest intensity in all directions of its cone.
+    phi: f64,
+    /// Unit axis of the cone of normals of the light
+    direction: Vec3,
+    /// Cosine of the largest angle between `direction` and a normal of the light
+    cos_theta_o: f64,
+    /// Cosine of the largest angle between a normal and a direction the light emits into
+    cos_theta_e: f64,
+    /// Whether the light emits along both sides of its normals
+    two_sided: bool,
+}
+
+impl LightBounds {
+    /// Returns a new instance of the `LightBounds` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `min`, `max`: Corners of the box that contains the light.
+    /// * `phi`: Power of the light, as if it emitted its largest intensity everywhere.
+    /// * `direction`: Axis of the cone of normals of the light.
+    /// * `theta_o`: Angle between the axis and the normal furthest from it in radians.
+    /// * `theta_e`: Angle between a normal and the directions of emission in radians, e.g.
+    ///   `π/2` for surfaces.
+    pub fn new(
+        min: Point3,
+        max: Point3,
+        phi: f64,
+        direction: Vec3,
+        theta_o: f64,
+        theta_e: f64,
+    ) -> Self {
+        Self {
+            min,
+            max,
+            phi,
+            direction: direction.unit_vector(),
+            cos_theta_o: theta_o.min(PI).cos(),
+            cos_theta_e: theta_e.min(PI).cos(),
+            two_sided: false,
+        }
+    }
+
+    /// Returns the bounds of a light at a single point that emits into all directions.
+    pub fn point(p: Point3, phi: f64) -> Self {
+        Self::new(p, p, phi, Vec3::new(0.0, 0.0, 1.0), PI, PI / 2.0)
+    }
+
+    /// Lets the light emit along both sides of its normals (default: `false`).
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn phi(&self) -> f64 {
+        self.phi
+    }
+
+    fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    fn contains(&self, p: Point3) -> bool {
+        (self.min.x()..=self.max.x()).contains(&p.x())
+            && (self.min.y()..=self.max.y()).contains(&p.y())
+            && (self.min.z()..=self.max.z()).contains(&p.z())
+    }
+
+    /// Returns bounds that contain both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        let min = Vec3::new(
+            self.min.x().min(other.min.x()),
+            self.min.y().min(other.min.y()),
+            self.min.z().min(other.min.z()),
+        );
+        let max = Vec3::new(
+            self.max.x().max(other.max.x()),
+            self.max.y().max(other.max.y()),
+            self.max.z().max(other.max.z()),
+        );
+        let (direction, cos_theta_o) = union_cones(
+            (self.direction, self.cos_theta_o),
+            (other.direction, other.cos_theta_o),
+        );
+
+        Self {
+            min,
+            max,
+            phi: self.phi + other.phi,
+            direction,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    /// Returns a conservative estimate of the light that arrives at `p` (Conty Estevez and Kulla,
+    /// "Importance Sampling of Many Lights with Adaptive Tree Splitting"): the power divided by
+    /// the squared distance, times the cosine of the smallest angle between an emission
+    /// direction and the direction to `p`.
+    fn importance(&self, p: Point3) -> f64 {
+        if self.phi <= 0.0 {
+            return 0.0;
+        }
+
+        // the distance is clamped to the size of the bounds, so close lights do not dominate
+        let half_diagonal = 0.5 * (self.max - self.min).length();
+        let to_p = p - self.centroid();
+        let d2 = to_p.length_squared().max(half_diagonal * half_diagonal);
+        if self.contains(p) || d2 <= 0.0 {
+            return self.phi / d2.max(f64::MIN_POSITIVE);
+        }
+
+        let mut cos_theta_w = self.direction.dot(to_p.unit_vector());
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = (1.0 - cos_theta_w * cos_theta_w).max(0.0).sqrt();
+
+        // angle of the cone from `p` that contains the bounds
+        let cos_theta_b = match to_p.length_squared() > half_diagonal * half_diagonal {
+            true => (1.0 - half_diagonal * half_diagonal / to_p.length_squared()).sqrt(),
+            false => -1.0,
+        };
+        let sin_theta_b = (1.0 - cos_theta_b * cos_theta_b).max(0.0).sqrt();
+
+        // subtract the angles of the normals and the bounds from the angle to `p`
+        let sin_theta_o = (1.0 - self.cos_theta_o * self.cos_theta_o).max(0.0).sqrt();
+        let (sin_theta_x, cos_theta_x) =
+            subtract_angle((sin_theta_w, cos_theta_w), (sin_theta_o, self.cos_theta_o));
+        let (_, cos_theta_p) =
+            subtract_angle((sin_theta_x, cos_theta_x), (sin_theta_b, cos_theta_b));
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        self.phi * cos_theta_p / d2
+    }
+}
+
+/// Returns the sine and cosine of the difference of angle `a` and `b`, clamped to `0` if `b` is
+/// larger.
+fn subtract_angle((sin_a, cos_a): (f64, f64), (sin_b, cos_b): (f64, f64)) -> (f64, f64) {
+    match cos_a > cos_b {
+        true => (0.0, 1.0),
+        false => (sin_a * cos_b - cos_a * sin_b, cos_a * cos_b + sin_a * sin_b),
+    }
+}
+
+/// Returns the axis and cosine of the angle of the smallest cone that contains the cones `a` and
+/// `b`, given by their axes and cosines.
+fn union_cones(a: (Vec3, f64), b: (Vec3, f64)) -> (Vec3, f64) {
+    let theta_a = a.1.clamp(-1.0, 1.0).acos();
+    let theta_b = b.1.clamp(-1.0, 1.0).acos();
+    let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    let axis = a.0.cross(b.0);
+    if theta_o >= PI || axis.length_squared() == 0.0 {
+        return (a.0, -1.0);
+    }
+
+    // rotate the axis of `a` towards `b` (Rodrigues' formula)
+    let axis = axis.unit_vector();
+    let (sin, cos) = (theta_o - theta_a).sin_cos();
+    let direction = a.0 * cos + axis.cross(a.0) * sin + axis * (axis.dot(a.0) * (1.0 - cos));
+    (direction.unit_vector(), theta_o.cos())
+}
+
+/// Strategies for choosing the light to sample from a point, parsed from their lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightSamplerKind {
+    /// Every light with the same probability
+    Uniform,
+    /// Lights in proportion to their power
+    Power,
+    /// Lights in proportion to their estimated contribution at the point, found in a bounding
+    /// volume hierarchy over the lights
+    Bvh,
+}
+
+impl FromStr for LightSamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Self::Uniform),
+            "power" => Ok(Self::Power),
+            "bvh" => Ok(Self::Bvh),
+            _ => Err(format!(
+                "unknown light sampler `{}` (expected `uniform`, `power` or `bvh`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Light chosen by a `LightSampler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChoice {
+    /// Light at an index of the list of lights
+    Light(usize),
+    Environment,
+}
+
+/// Chooses one of the lights of a scene to sample from a point.
+///
+/// With the `Power` and `Bvh` strategies, lights without bounds (e.g. the environment and
+/// directional lights) are chosen in proportion to their power, against the total power of all
+/// bounded lights together. One of those is then chosen by the strategy.
+pub struct LightSampler {
+    /// Lights that are chosen directly, all of them for `Uniform`
+    fixed: Vec<LightChoice>,
+    /// Distribution over the lights in `fixed` and, as the last interval, the bounded lights
+    distribution: Option<Distribution1D>,
+    bounded: BoundedLights,
+}
+
+enum BoundedLights {
+    None,
+    Power {
+        /// Index of every light with bounds, in the order of the distribution
+        lights: Vec<usize>,
+        /// Position of every light in `lights`, by light index
+        positions: Vec<Option<usize>>,
+        distribution: Distribution1D,
+    },
+    Bvh(LightBvh),
+}
+
+impl LightSampler {
+    /// Returns the sampler for lights with `bounds` (`None` for unbounded lights) and `powers`,
+    /// and an importance sampled environment with its power or no environment (`None`).
+    ///
+    /// The powers of lights with bounds are not used, their bounds hold their power.
+    pub fn new(
+        kind: LightSamplerKind,
+        bounds: &[Option<LightBounds>],
+        powers: &[f64],
+        environment: Option<f64>,
+    ) -> Self {
+        assert_eq!(
+            bounds.len(),
+            powers.len(),
+            "every light needs bounds and a power"
+        );
+
+        let mut fixed: Vec<(LightChoice, f64)> = match kind {
+            LightSamplerKind::Uniform => (0..bounds.len())
+                .map(|i| (LightChoice::Light(i), 1.0))
+                .collect(),
+            _ => (0..bounds.len())
+                .filter(|&i| bounds[i].is_none())
+                .map(|i| (LightChoice::Light(i), powers[i]))
+                .collect(),
+        };
+        if let Some(power) = environment {
+            let power = match kind {
+                LightSamplerKind::Uniform => 1.0,
+                _ => power,
+            };
+            fixed.push((LightChoice::Environment, power));
+        }
+
+        let bounded: Vec<(usize, LightBounds)> = bounds
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bounds)| Some((i, (*bounds)?)))
+            .collect();
+        let bounded_power: f64 = bounded.iter().map(|(_, bounds)| bounds.phi).sum();
+        let bounded = match kind {
+            _ if bounded.is_empty() => BoundedLights::None,
+            LightSamplerKind::Uniform => BoundedLights::None,
+            LightSamplerKind::Power => {
+                let mut positions = vec![None; bounds.len()];
+                for (position, &(i, _)) in bounded.iter().enumerate() {
+                    positions[i] = Some(position);
+                }
+                BoundedLights::Power {
+                    lights: bounded.iter().map(|&(i, _)| i).collect(),
+                    positions,
+                    distribution: Distribution1D::new(
+                        bounded.iter().map(|(_, bounds)| bounds.phi).collect(),
+                    ),
+                }
+            }
+            LightSamplerKind::Bvh => BoundedLights::Bvh(LightBvh::new(bounded, bounds.len())),
+        };
+
+        let mut function: Vec<f64> = fixed.iter().map(|&(_, power)| power).collect();
+        if !matches!(bounded, BoundedLights::None) {
+            function.push(bounded_power);
+        }
+
+        Self {
+            fixed: fixed.into_iter().map(|(choice, _)| choice).collect(),
+            distribution: (!function.is_empty()).then(|| Distribution1D::new(function)),
+            bounded,
+        }
+    }
+
+    /// Chooses a light to sample from `p` with `u`. Returns the light and the probability of
+    /// choosing it.
+    pub fn choose(&self, p: Point3, u: f64) -> Option<(LightChoice, f64)> {
+        let distribution = self.distribution.as_ref()?;
+        let (x, _, slot) = distribution.sample(u);
+        let probability = distribution.interval_probability(slot);
+        if let Some(&choice) = self.fixed.get(slot) {
+            return Some((choice, probability));
+        }
+
+        // reuse the position of `u` in the interval of the bounded lights
+        let slots = self.fixed.len() + 1;
+        let u = (x * slots as f64 - slot as f64).clamp(0.0, 1.0);
+        let (light, pmf) = match &self.bounded {
+            BoundedLights::None => return None,
+            BoundedLights::Power {
+                lights,
+                distribution,
+                ..
+            } => {
+                let (_, _, index) = distribution.sample(u);
+                (lights[index], distribution.interval_probability(index))
+            }
+            BoundedLights::Bvh(bvh) => bvh.choose(p, u)?,
+        };
+        Some((LightChoice::Light(light), pmf * probability))
+    }
+
+    /// Returns the probability that `choose` picks `choice` from `p`.
+    pub fn pmf(&self, p: Point3, choice: LightChoice) -> f64 {
+        let Some(distribution) = &self.distribution else {
+            return 0.0;
+        };
+        if let Some(slot) = self.fixed.iter().position(|&fixed| fixed == choice) {
+            return distribution.interval_probability(slot);
+        }
+
+        let LightChoice::Light(light) = choice else {
+            return 0.0;
+        };
+        let pmf = match &self.bounded {
+            BoundedLights::None => return 0.0,
+            BoundedLights::Power {
+                positions,
+                distribution,
+                ..
+            } => match positions.get(light).copied().flatten() {
+                Some(position) => distribution.interval_probability(position),
+                None => 0.0,
+            },
+            BoundedLights::Bvh(bvh) => bvh.pmf(p, light),
+        };
+        pmf * distribution.interval_probability(self.fixed.len())
+    }
+}
+
+/// Binary tree over bounded lights, where every node holds the bounds of the lights below it. A
+/// light is chosen by descending from the root into each child with a probability proportional
+/// to its importance at the point.
+struct LightBvh {
+    nodes: Vec<LightBvhNode>,
+    /// Path from the root to the leaf of every light, by light index: the branches as bits (`1`
+    /// for the second child), starting at the lowest bit, and the depth of the leaf
+    trails: Vec<Option<(u64, u32)>>,
+}
+
+struct LightBvhNode {
+    bounds: LightBounds,
+    /// Index of the light of a leaf, or the second child of an interior node (the first child
+    /// directly follows the node)
+    index: usize,
+    leaf: bool,
+}
+
+impl LightBvh {
+    /// Returns the hierarchy over the `lights` with their indices. `count` is the number of all
+    /// lights.
+    fn new(mut lights: Vec<(usize, LightBounds)>, count: usize) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * lights.len()),
+            trails: vec![None; count],
+        };
+        bvh.build(&mut lights, 0, 0);
+        bvh
+    }
+
+    /// Adds the subtree over `lights` at the path `trail` with `depth` and returns its index.
+    fn build(&mut self, lights: &mut [(usize, LightBounds)], trail: u64, depth: u32) -> usize {
+        let node = self.nodes.len();
+        if let [(light, bounds)] = lights {
+            self.nodes.push(LightBvhNode {
+                bounds: *bounds,
+                index: *light,
+                leaf: true,
+            });
+            self.trails[*light] = Some((trail, depth));
+            return node;
+        }
+
+        let bounds = lights[1..]
+            .iter()
+            .fold(lights[0].1, |bounds, (_, other)| bounds.union(other));
+
+        // split at the median of the centroids along the axis where they spread the most
+        let centroids: Vec<Point3> = lights.iter().map(|(_, b)| b.centroid()).collect();
+        let extent = |axis: fn(&Point3) -> f64| {
+            let values = centroids.iter().map(axis);
+            values.clone().fold(f64::NEG_INFINITY, f64::max) - values.fold(f64::INFINITY, f64::min)
+        };
+        let axes: [fn(&Point3) -> f64; 3] = [Point3::x, Point3::y, Point3::z];
+        let axis = axes
+            .into_iter()
+            .max_by(|a, b| extent(*a).total_cmp(&extent(*b)))
+            .unwrap();
+        lights.sort_by(|(_, a), (_, b)| axis(&a.centroid()).total_cmp(&axis(&b.centroid())));
+
+        self.nodes.push(LightBvhNode {
+            bounds,
+            index: 0,
+            leaf: false,
+        });
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+        self.build(first, trail, depth + 1);
+        self.nodes[node].index = self.build(second, trail | 1 << depth, depth + 1);
+        node
+    }
+
+    /// Returns the probabilities of descending into the first and second child of `node` from
+    /// `p`, or `None` if neither contributes.
+    fn child_probabilities(&self, node: usize, p: Point3) -> Option<(f64, f64)> {
+        let first = self.nodes[node + 1].bounds.importance(p);
+        let second = self.nodes[self.nodes[node].index].bounds.importance(p);
+        match first + second > 0.0 {
+            true => Some((first / (first + second), second / (first + second))),
+            false => None,
+        }
+    }
+
+    fn choose(&self, p: Point3, mut u: f64) -> Option<(usize, f64)> {
+        if self.nodes[0].bounds.importance(p) <= 0.0 {
+            return None;
+        }
+
+        let (mut node, mut pmf) = (0, 1.0);
+        while !self.nodes[node].leaf {
+            let (first, second) = self.child_probabilities(node, p)?;
+            if u < first {
+                u = (u / first).min(1.0);
+                pmf *= first;
+                node += 1;
+            } else {
+                u = ((u - first) / second).clamp(0.0, 1.0);
+                pmf *= second;
+                node = self.nodes[node].index;
+            }
+        }
+
+        Some((self.nodes[node].index, pmf))
+    }
+
+    fn pmf(&self, p: Point3, light: usize) -> f64 {
+        let Some((trail, depth)) = self.trails.get(light).copied().flatten() else {
+            return 0.0;
+        };
+        if self.nodes[0].bounds.importance(p) <= 0.0 {
+            return 0.0;
+        }
+
+        let (mut node, mut pmf) = (0, 1.0);
+        for level in 0..depth {
+            let Some((first, second)) = self.child_probabilities(node, p) else {
+                return 0.0;
+            };
+            match trail >> level & 1 {
+                0 => {
+                    pmf *= first;
+                    node += 1;
+                }
+                _ => {
+                    pmf *= second;
+                    node = self.nodes[node].index;
+                }
+            }
+        }
+
+        pmf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lights at different distances and with different powers, and one without bounds.
+    fn bounds() -> Vec<Option<LightBounds>> {
+        vec![
+            Some(LightBounds::point(Point3::new(0.0, 2.0, 0.0), 4.0)),
+            None,
+            Some(LightBounds::point(Point3::new(5.0, 0.0, 0.0), 1.0)),
+            Some(
+                LightBounds::new(
+                    Point3::new(-1.0, 3.0, -1.0),
+                    Point3::new(1.0, 3.0, 1.0),
+                    10.0,
+                    Vec3::new(0.0, -1.0, 0.0),
+                    0.0,
+                    PI / 2.0,
+                )
+                .two_sided(true),
+            ),
+            Some(LightBounds::point(Point3::new(0.0, -4.0, 2.0), 2.0)),
+        ]
+    }
+
+    /// Power of every light, only used for the one without bounds.
+    fn powers() -> Vec<f64> {
+        vec![0.0, 8.0, 0.0, 0.0, 0.0]
+    }
+
+    fn choices(lights: usize) -> impl Iterator<Item = LightChoice> {
+        (0..lights)
+            .map(LightChoice::Light)
+            .chain([LightChoice::Environment])
+    }
+
+    #[test]
+    fn choose_agrees_with_pmf() {
+        let bounds = bounds();
+        let p = Point3::new(0.5, 0.0, 0.5);
+
+        for kind in [
+            LightSamplerKind::Uniform,
+            LightSamplerKind::Power,
+            LightSamplerKind::Bvh,
+        ] {
+            for environment in [false, true] {
+                let sampler =
+                    LightSampler::new(kind, &bounds, &powers(), environment.then_some(12.0));
+
+                let total: f64 = choices(bounds.len()).map(|c| sampler.pmf(p, c)).sum();
+                assert!(
+                    (total - 1.0).abs() < 1e-9,
+                    "{:?}: pmf sums to {}",
+                    kind,
+                    total
+                );
+
+                let n = 10_000;
+                let mut counts = vec![0; bounds.len() + 1];
+                for i in 0..n {
+                    let u = (i as f64 + 0.5) / n as f64;
+                    let (choice, pmf) = sampler.choose(p, u).unwrap();
+                    assert!((pmf - sampler.pmf(p, choice)).abs() < 1e-9, "{:?}", kind);
+
+                    match choice {
+                        LightChoice::Light(light) => counts[light] += 1,
+                        LightChoice::Environment => counts[bounds.len()] += 1,
+                    }
+                }
+
+                for (choice, count) in choices(bounds.len()).zip(counts) {
+                    let frequency = count as f64 / n as f64;
+                    assert!(
+                        (frequency - sampler.pmf(p, choice)).abs() < 0.01,
+                        "{:?}: {:?} chosen with frequency {}",
+                        kind,
+                        choice,
+                        frequency
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn environment_is_not_chosen_without_it() {
+        let sampler = LightSampler::new(LightSamplerKind::Power, &bounds(), &powers(), None);
+        assert_eq!(
+            sampler.pmf(Point3::default(), LightChoice::Environment),
+            0.0
+        );
+    }
+
+    #[test]
+    fn unbounded_lights_are_chosen_by_power() {
+        // the bounded lights have a power of 17 together
+        let sampler = LightSampler::new(
+            LightSamplerKind::Bvh,
+            &bounds(),
+            &[0.0, 17.0, 0.0, 0.0, 0.0],
+            Some(34.0),
+        );
+        let p = Point3::new(0.5, 0.0, 0.5);
+        assert!((sampler.pmf(p, LightChoice::Environment) - 0.5).abs() < 1e-12);
+        assert!((sampler.pmf(p, LightChoice::Light(1)) - 0.25).abs() < 1e-12);
+
+        let bounded: f64 = [0, 2, 3, 4]
+            .map(|light| sampler.pmf(p, LightChoice::Light(light)))
+            .iter()
+            .sum();
+        assert!((bounded - 0.25).abs() < 1e-12);
+
+        // a dark environment is not chosen at all
+        let sampler = LightSampler::new(LightSamplerKind::Power, &bounds(), &powers(), Some(0.0));
+        assert_eq!(sampler.pmf(p, LightChoice::Environment), 0.0);
+    }
+
+    #[test]
+    fn no_lights() {
+        let sampler = LightSampler::new(LightSamplerKind::Bvh, &[], &[], None);
+        assert!(sampler.choose(Point3::default(), 0.5).is_none());
+
+        let sampler = LightSampler::new(LightSamplerKind::Bvh, &[], &[], Some(1.0));
+        assert_eq!(
+            sampler.choose(Point3::default(), 0.5),
+            Some((LightChoice::Environment, 1.0))
+        );
+    }
+
+    #[test]
+    fn parse_kind() {
+        assert_eq!("uniform".parse(), Ok(LightSamplerKind::Uniform));
+        assert_eq!("power".parse(), Ok(LightSamplerKind::Power));
+        assert_eq!("bvh".parse(), Ok(LightSamplerKind::Bvh));
+        assert!("BVH".parse::<LightSamplerKind>().is_err());
+    }
+}
@@ -0,0 +1,566 @@
+use super::{
+    environment::GradientSky,
+    hittables::TriangleMesh,
+    ies::IesProfile,
+    light_samplers::{LightBounds, LightChoice, LightSampler, LightSamplerKind},
+    materials::DiffuseLight,
+    sampling::{self, Distribution1D},
+    traits::{Environment, Light, Sampler},
+    Color, HitRecord, Onb, Point3, Ray, Vec3,
+};
+use std::{cell::OnceCell, f64::consts::PI, rc::Rc};
+
+/// Number of rows of points on a triangle used to estimate its average emission.
+const EMISSION_ESTIMATE_STEPS: usize = 4;
+
+/// Light arriving at a point from a sampled direction.
+pub struct LightSample {
+    /// Radiance arriving at the point, including the falloff with distance
+    pub radiance: Color,
+    /// Unit direction from the point towards the light
+    pub wi: Vec3,
+    /// Distance to the light, `f64::INFINITY` for distant lights
+    pub distance: f64,
+    /// Density of sampling the direction, `1` for lights from a single direction
+    pub pdf: f64,
+    /// Whether the light arrives from a single point or direction, which rays cannot hit by
+    /// chance
+    pub delta: bool,
+}
+
+/// Lights of a scene, next to the `HittableList` with its objects, and the environment that
+/// surrounds it.
+pub struct LightList {
+    lights: Vec<Box<dyn Light>>,
+    environment: Box<dyn Environment>,
+    light_sampler: LightSamplerKind,
+    /// Radius of a sphere around the objects of the scene
+    scene_radius: f64,
+    /// Chooses the light to sample, built on first use after the lights changed
+    sampler: OnceCell<LightSampler>,
+}
+
+impl Default for LightList {
+    /// No lights, with a `GradientSky` as environment, chosen by their power.
+    fn default() -> Self {
+        Self {
+            lights: Vec::new(),
+            environment: Box::new(GradientSky::default()),
+            light_sampler: LightSamplerKind::Power,
+            scene_radius: 1.0,
+            sampler: OnceCell::new(),
+        }
+    }
+}
+
+impl LightList {
+    /// Adds a light and returns its index, which marks the surface of an `AreaLight` with
+    /// `Mesh::light`.
+    pub fn add(&mut self, light: Box<dyn Light>) -> usize {
+        self.lights.push(light);
+        self.sampler = OnceCell::new();
+        self.lights.len() - 1
+    }
+
+    pub fn set_environment(&mut self, environment: Box<dyn Environment>) {
+        self.environment = environment;
+        self.sampler = OnceCell::new();
+    }
+
+    /// Sets the strategy for choosing the light to sample (default: `Power`).
+    pub fn set_light_sampler(&mut self, light_sampler: LightSamplerKind) {
+        self.light_sampler = light_sampler;
+        self.sampler = OnceCell::new();
+    }
+
+    /// Sets the radius of a sphere around the objects of the scene (default: `1`). The light
+    /// of the environment and of lights infinitely far away that falls onto the scene grows
+    /// with it, which makes them more likely to be chosen than the other lights.
+    pub fn set_scene_radius(&mut self, radius: f64) {
+        assert!(radius > 0.0, "scene radius should be positive");
+        self.scene_radius = radius;
+        self.sampler = OnceCell::new();
+    }
+
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    /// Returns whether there is nothing to sample.
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty() && !self.environment.importance_sampled()
+    }
+
+    fn sampler(&self) -> &LightSampler {
+        self.sampler.get_or_init(|| {
+            let bounds: Vec<_> = self.lights.iter().map(|light| light.bounds()).collect();
+            let powers: Vec<_> = self
+                .lights
+                .iter()
+                .map(|light| light.power(self.scene_radius))
+                .collect();
+            LightSampler::new(
+                self.light_sampler,
+                &bounds,
+                &powers,
+                self.environment
+                    .importance_sampled()
+                    .then(|| self.environment.power(self.scene_radius)),
+            )
+        })
+    }
+
+    /// Samples a light chosen from `p`, the probability of the choice is included in the density
+    /// of the sample.
+    pub fn sample(&self, p: Point3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let (choice, pmf) = self.sampler().choose(p, sampler.get_1d())?;
+        let mut sample = match choice {
+            LightChoice::Light(index) => self.lights[index].sample_li(p, sampler)?,
+            LightChoice::Environment => self.environment.sample_li(sampler)?,
+        };
+        sample.pdf *= pmf;
+        Some(sample)
+    }
+
+    /// Returns the density with which `sample` picks the direction of `r` from its origin
+    /// towards the hit `hitrecord` of `r`, which is `0` unless the surface belongs to a light.
+    pub fn pdf(&self, r: &Ray, hitrecord: &HitRecord) -> f64 {
+        let Some((index, primitive)) = hitrecord.emitter() else {
+            return 0.0;
+        };
+        match self.lights[index].pdf_li(r, primitive, hitrecord.get_t()) {
+            pdf if pdf > 0.0 => pdf * self.sampler().pmf(r.origin(), LightChoice::Light(index)),
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the density with which `sample` picks the direction of `r` from its origin
+    /// towards the environment.
+    pub fn environment_pdf(&self, r: &Ray) -> f64 {
+        match self.environment.importance_sampled() {
+            true => {
+                self.environment.pdf(r.direction())
+                    * self.sampler().pmf(r.origin(), LightChoice::Environment)
+            }
+            false => 0.0,
+        }
+    }
+}
+
+/// Infinitely small light that shines equally in all directions.
+pub struct PointLight {
+    position: Point3,
+    color: Color,
+    /// Radiant intensity, the irradiance at a distance of 1
+    intensity: f64,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, color: Color, intensity: f64) -> Self {
+        Self {
+            position,
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+
+        Some(LightSample {
+            radiance: self.color * (self.intensity / (distance * distance)),
+            wi: to_light / distance,
+            distance,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.intensity * self.color.luminance();
+        Some(LightBounds::point(self.position, phi))
+    }
+}
+
+/// Point light that only shines into a cone, with a smooth falloff towards its edge.
+pub struct SpotLight {
+    position: Point3,
+    /// Unit direction of the axis of the cone
+    direction: Vec3,
+    color: Color,
+    /// Radiant intensity along the axis
+    intensity: f64,
+    /// Cosine of the angle between the axis and the edge of the cone
+    cos_total: f64,
+    /// Cosine of the angle where the falloff starts
+    cos_falloff_start: f64,
+}
+
+impl SpotLight {
+    /// Returns a new instance of the `SpotLight` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `target`: Point the light is aimed at.
+    /// * `intensity`: Radiant intensity along the axis, the irradiance at a distance of 1.
+    /// * `cone_angle`: Angle between the axis and the edge of the cone in degrees.
+    /// * `falloff_start`: Angle (in degrees) from which the intensity falls off to 0 at the edge.
+    pub fn new(
+        position: Point3,
+        target: Point3,
+        color: Color,
+        intensity: f64,
+        cone_angle: f64,
+        falloff_start: f64,
+    ) -> Self {
+        assert!(
+            (0.0..=cone_angle).contains(&falloff_start) && cone_angle <= 180.0,
+            "falloff should start inside a cone of at most 180 degrees"
+        );
+
+        Self {
+            position,
+            direction: (target - position).unit_vector(),
+            color,
+            intensity,
+            cos_total: cone_angle.to_radians().cos(),
+            cos_falloff_start: falloff_start.to_radians().cos(),
+        }
+    }
+
+    /// Returns the fraction of the intensity emitted in a direction with `cos_theta` to the axis.
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total {
+            return 0.0;
+        }
+
+        let t = (cos_theta - self.cos_total) / (self.cos_falloff_start - self.cos_total);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        let wi = to_light / distance;
+
+        let falloff = self.falloff((-wi).dot(self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            radiance: self.color * (self.intensity * falloff / (distance * distance)),
+            wi,
+            distance,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.intensity * self.color.luminance();
+        let theta_o = self.cos_falloff_start.acos();
+        let theta_e = self.cos_total.acos() - theta_o;
+        Some(LightBounds::new(
+            self.position,
+            self.position,
+            phi,
+            self.direction,
+            theta_o,
+            theta_e,
+        ))
+    }
+}
+
+/// Point light with the intensity distribution of a measured fixture, e.g. a downlight or a wall
+/// washer, given by an IES profile.
+///
+/// The profile only shapes the light: it is scaled so that the brightest direction has the
+/// intensity of the light.
+pub struct IesLight {
+    position: Point3,
+    /// Unit direction of the zenith of the profile, opposite to the nadir
+    zenith: Vec3,
+    /// Unit directions of the `C0` and `C90` planes of the profile
+    c0: Vec3,
+    c90: Vec3,
+    profile: Rc<IesProfile>,
+    color: Color,
+    /// Radiant intensity in the brightest direction
+    intensity: f64,
+}
+
+impl IesLight {
+    /// Returns a new instance of the `IesLight` struct, with the nadir of the profile pointing
+    /// down (`-y`) and its `C0` plane towards `+x`.
+    ///
+    /// Arguments:
+    ///
+    /// * `intensity`: Radiant intensity in the brightest direction, the irradiance at a distance
+    ///   of 1.
+    pub fn new(position: Point3, profile: Rc<IesProfile>, color: Color, intensity: f64) -> Self {
+        Self {
+            position,
+            zenith: Vec3::new(0.0, 1.0, 0.0),
+            c0: Vec3::new(1.0, 0.0, 0.0),
+            c90: Vec3::new(0.0, 0.0, -1.0),
+            profile,
+            color,
+            intensity,
+        }
+    }
+
+    /// Aims the nadir of the profile at `target`. The `C0` plane faces `+x` (or `+z` when aiming
+    /// along the `x` axis), rotated by `rotation` degrees counterclockwise seen from the zenith.
+    pub fn aim(mut self, target: Point3, rotation: f64) -> Self {
+        let zenith = (self.position - target).unit_vector();
+        let reference = match zenith.x().abs() > 0.9 {
+            true => Vec3::new(0.0, 0.0, 1.0),
+            false => Vec3::new(1.0, 0.0, 0.0),
+        };
+        let c0 = (reference - reference.dot(zenith) * zenith).unit_vector();
+        let c90 = zenith.cross(c0);
+
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        self.zenith = zenith;
+        self.c0 = cos * c0 + sin * c90;
+        self.c90 = cos * c90 - sin * c0;
+        self
+    }
+
+    /// Returns the intensity of the profile along the unit `direction` relative to its maximum.
+    fn falloff(&self, direction: Vec3) -> f64 {
+        let max = self.profile.max_candela();
+        if max <= 0.0 {
+            return 0.0;
+        }
+
+        let vertical = (-direction.dot(self.zenith))
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees();
+        let horizontal = direction
+            .dot(self.c90)
+            .atan2(direction.dot(self.c0))
+            .to_degrees();
+        self.profile.candela(vertical, horizontal) / max
+    }
+}
+
+impl Light for IesLight {
+    fn sample_li(&self, p: Point3, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        let wi = to_light / distance;
+
+        let falloff = self.falloff(-wi);
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            radiance: self.color * (self.intensity * falloff / (distance * distance)),
+            wi,
+            distance,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.intensity * self.color.luminance();
+        Some(LightBounds::point(self.position, phi))
+    }
+}
+
+/// Light from a far away source such as the sun, that arrives from (nearly) the same direction
+/// everywhere in the scene. With an angular diameter the source is a disk, which gives soft
+/// shadows.
+pub struct DirectionalLight {
+    /// Unit direction towards the light
+    direction: Vec3,
+    color: Color,
+    /// Irradiance on a surface facing the light
+    irradiance: f64,
+    /// Cosine of the angular radius of the source
+    cos_radius: f64,
+}
+
+impl DirectionalLight {
+    /// Returns a new instance of the `DirectionalLight` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `direction`: Direction towards the light.
+    /// * `irradiance`: Irradiance on a surface facing the light.
+    pub fn new(direction: Vec3, color: Color, irradiance: f64) -> Self {
+        Self {
+            direction: direction.unit_vector(),
+            color,
+            irradiance,
+            cos_radius: 1.0,
+        }
+    }
+
+    /// Sets the apparent size of the source in degrees, e.g. `0.53` for the sun.
+    pub fn angular_diameter(mut self, degrees: f64) -> Self {
+        assert!(
+            (0.0..180.0).contains(&degrees),
+            "angular diameter should be between 0 and 180 degrees"
+        );
+        self.cos_radius = (degrees / 2.0).to_radians().cos();
+        self
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: Point3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        // uniform direction in the cone of the disk, the irradiance is spread evenly over it
+        let local = sampling::sample_uniform_cone(sampler.get_2d(), self.cos_radius);
+
+        Some(LightSample {
+            radiance: self.color * self.irradiance,
+            wi: Onb::from_w(self.direction).local_to_world(local),
+            distance: f64::INFINITY,
+            pdf: 1.0,
+            delta: true,
+        })
+    }
+
+    fn power(&self, scene_radius: f64) -> f64 {
+        PI * scene_radius * scene_radius * self.irradiance * self.color.luminance()
+    }
+}
+
+/// Triangles of a mesh that glow with a `DiffuseLight`. The mesh has to be added to the objects
+/// of the scene as a `Mesh` with the same material to be seen by rays, marked with `Mesh::light`
+/// so that hits on it are weighted against light sampling.
+///
+/// The triangles are chosen in proportion to the power they emit and points on them uniformly.
+pub struct AreaLight {
+    mesh: Rc<TriangleMesh>,
+    emitter: Rc<DiffuseLight>,
+    /// Distribution of the emitted power over the triangles
+    distribution: Distribution1D,
+    bounds: LightBounds,
+}
+
+impl AreaLight {
+    pub fn new(mesh: Rc<TriangleMesh>, emitter: Rc<DiffuseLight>) -> Self {
+        assert!(!mesh.is_empty(), "area light needs at least one triangle");
+
+        // the power is the area times the average radiance, estimated on a grid of points
+        let steps = EMISSION_ESTIMATE_STEPS;
+        let sides = match emitter.is_two_sided() {
+            true => 2.0,
+            false => 1.0,
+        };
+        let power = (0..mesh.len())
+            .map(|index| {
+                let mut luminance = 0.0;
+                for i in 0..steps {
+                    for j in 0..steps {
+                        let u = (
+                            (i as f64 + 0.5) / steps as f64,
+                            (j as f64 + 0.5) / steps as f64,
+                        );
+                        let b = sampling::sample_uniform_triangle(u);
+                        let radiance =
+                            emitter.radiance(mesh.uv(index, b), mesh.point(index, b), true);
+                        luminance += radiance.luminance().max(0.0);
+                    }
+                }
+                sides * mesh.area(index) * luminance / (steps * steps) as f64
+            })
+            .collect::<Vec<_>>();
+
+        let bounds = (0..mesh.len())
+            .filter(|&index| mesh.area(index) > 0.0)
+            .map(|index| {
+                let [p0, p1, p2] = mesh.vertices(index);
+                let min = Vec3::new(
+                    p0.x().min(p1.x()).min(p2.x()),
+                    p0.y().min(p1.y()).min(p2.y()),
+                    p0.z().min(p1.z()).min(p2.z()),
+                );
+                let max = Vec3::new(
+                    p0.x().max(p1.x()).max(p2.x()),
+                    p0.y().max(p1.y()).max(p2.y()),
+                    p0.z().max(p1.z()).max(p2.z()),
+                );
+                let phi = PI * power[index];
+                LightBounds::new(min, max, phi, mesh.normal(index), 0.0, PI / 2.0)
+                    .two_sided(emitter.is_two_sided())
+            })
+            .reduce(|bounds, other| bounds.union(&other))
+            .expect("area light needs a triangle with an area");
+
+        Self {
+            mesh,
+            emitter,
+            distribution: Distribution1D::new(power),
+            bounds,
+        }
+    }
+
+    /// Returns the solid angle density of choosing the point at distance `distance` along the
+    /// unit direction `wi` on the triangle `index`.
+    fn triangle_pdf(&self, index: usize, wi: Vec3, distance: f64) -> f64 {
+        let cos_theta = self.mesh.normal(index).dot(wi).abs();
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let area_pdf = self.distribution.interval_probability(index) / self.mesh.area(index);
+        area_pdf * distance * distance / cos_theta
+    }
+}
+
+impl Light for AreaLight {
+    fn sample_li(&self, p: Point3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let (_, _, index) = self.distribution.sample(sampler.get_1d());
+        let b = sampling::sample_uniform_triangle(sampler.get_2d());
+        let point = self.mesh.point(index, b);
+
+        let to_light = point - p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let wi = to_light / distance;
+
+        let front_face = self.mesh.normal(index).dot(wi) < 0.0;
+        let radiance = self
+            .emitter
+            .radiance(self.mesh.uv(index, b), point, front_face);
+        let pdf = self.triangle_pdf(index, wi, distance);
+        if radiance == Color::default() || pdf <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            radiance,
+            wi,
+            distance,
+            pdf,
+            delta: false,
+        })
+    }
+
+    fn pdf_li(&self, r: &Ray, primitive: usize, t: f64) -> f64 {
+        let length = r.direction().length();
+        self.triangle_pdf(primitive, r.direction() / length, t * length)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(self.bounds)
+    }
+}
@@ -1,4 +1,12 @@
-use super::{traits::Material, utils, Color, HitRecord, Ray, Vec3};
+use super::{
+    microfacet::{self, TrowbridgeReitz},
+    sampling,
+    spectrum::{Ior, SampledWavelengths},
+    thin_film::{self, ThinFilm},
+    traits::{FloatTexture, Material, Sampler, Texture},
+    Color, HitRecord, Onb, Point3, Ray, Vec3,
+};
+use std::{f64::consts::PI, rc::Rc};
 
 pub struct Lambertian {
     albedo: Color,
@@ -11,10 +19,16 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r_in: &Ray, hitrecord: &HitRecord) -> Option<(Color, Ray)> {
-        let scatter_direction = hitrecord.normal() + Vec3::random_unit_vector();
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let scatter_direction =
+            hitrecord.normal() + sampling::sample_uniform_sphere(sampler.get_2d());
 
-        // check that the `random_unit_vector` is not _almost_ the opposite of the hit normal
+        // check that the random unit vector is not _almost_ the opposite of the hit normal
         let scatter_direction = match scatter_direction.near_zero() {
             true => hitrecord.normal(),
             false => scatter_direction,
@@ -25,6 +39,262 @@ impl Material for Lambertian {
             Ray::new(hitrecord.get_inpact_point(), scatter_direction),
         ))
     }
+
+    fn eval(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        self.albedo * (wi.unit_vector().dot(hitrecord.normal()).max(0.0) / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        Some(sampling::cosine_hemisphere_pdf(
+            wi.unit_vector().dot(hitrecord.normal()),
+        ))
+    }
+}
+
+/// Rough diffuse material (e.g. clay, concrete or plaster) based on the Oren-Nayar model, which
+/// treats the surface as V-shaped diffuse microfacets. Unlike `Lambertian` it reflects more light
+/// back towards the light source and looks flatter.
+pub struct OrenNayar {
+    albedo: Rc<dyn Texture>,
+    /// Coefficients of the model, derived from the standard deviation of the facet angles
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    /// Returns a new instance of the `OrenNayar` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `sigma`: Standard deviation of the facet angles in degrees, `0` is Lambertian.
+    pub fn new(albedo: Rc<dyn Texture>, sigma: f64) -> Self {
+        assert!(sigma >= 0.0, "sigma cannot be negative");
+        let sigma2 = sigma.to_radians().powi(2);
+        Self {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    /// Returns the factor that scales the Lambertian reflection from `wi` towards `wo` (in the
+    /// local shading space).
+    fn roughness_factor(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let sin_theta = |w: Vec3| (1.0 - w.z() * w.z()).max(0.0).sqrt();
+        let (sin_i, sin_o) = (sin_theta(wi), sin_theta(wo));
+
+        // cosine of the azimuth difference
+        let max_cos = match sin_i > 1.0e-4 && sin_o > 1.0e-4 {
+            true => ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o)).max(0.0),
+            false => 0.0,
+        };
+        let (sin_alpha, tan_beta) = match wi.z().abs() > wo.z().abs() {
+            true => (sin_o, sin_i / wi.z().abs()),
+            false => (sin_i, sin_o / wo.z().abs().max(1.0e-4)),
+        };
+
+        self.a + self.b * max_cos * sin_alpha * tan_beta
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = sampling::sample_cosine_hemisphere(sampler.get_2d());
+
+        // the cosine sampling cancels the cosine and 1/pi of the diffuse term
+        let albedo = self
+            .albedo
+            .value(hitrecord.uv(), hitrecord.get_inpact_point());
+        Some((
+            albedo * self.roughness_factor(wo, wi),
+            Ray::new(hitrecord.get_inpact_point(), onb.local_to_world(wi)),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        if wi.z() <= 0.0 {
+            return Color::default();
+        }
+
+        let albedo = self
+            .albedo
+            .value(hitrecord.uv(), hitrecord.get_inpact_point());
+        albedo * (self.roughness_factor(wo, wi) * wi.z() / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        Some(sampling::cosine_hemisphere_pdf(
+            wi.unit_vector().dot(hitrecord.normal()),
+        ))
+    }
+}
+
+/// Cloth material: a diffuse base with a sheen lobe of fibers that scatter light at grazing
+/// angles, which gives the soft highlights along the silhouette of velvet and satin. The sheen
+/// uses the "Charlie" distribution (Estevez and Kulla, "Production Friendly Microfacet Sheen
+/// BRDF") with the visibility term of Ashikhmin.
+pub struct Velvet {
+    albedo: Rc<dyn Texture>,
+    sheen: Rc<dyn Texture>,
+    /// Roughness of the sheen in `(0, 1]`, lower values give a narrower rim
+    roughness: Rc<dyn FloatTexture>,
+}
+
+impl Velvet {
+    pub fn new(albedo: Rc<dyn Texture>, sheen: Rc<dyn Texture>) -> Self {
+        Self {
+            albedo,
+            sheen,
+            roughness: Rc::new(0.5),
+        }
+    }
+
+    pub fn roughness(mut self, roughness: Rc<dyn FloatTexture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Returns the BRDF for light from `wi` that scatters towards `wo` (in the local shading
+    /// space).
+    fn f(&self, hitrecord: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        let (uv, p) = (hitrecord.uv(), hitrecord.get_inpact_point());
+        let alpha = self.roughness.value(uv, p).clamp(1.0e-3, 1.0).powi(2);
+        let h = (wi + wo).unit_vector();
+        let sin_h = (1.0 - h.z() * h.z()).max(0.0).sqrt();
+        let distribution = (2.0 + 1.0 / alpha) * sin_h.powf(1.0 / alpha) / (2.0 * PI);
+        let visibility = 1.0 / (4.0 * (wi.z() + wo.z() - wi.z() * wo.z()));
+
+        self.albedo.value(uv, p) * (1.0 / PI)
+            + self.sheen.value(uv, p) * (distribution * visibility)
+    }
+}
+
+impl Material for Velvet {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = sampling::sample_cosine_hemisphere(sampler.get_2d());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+
+        // weighted by cosine / pdf = pi
+        Some((
+            self.f(hitrecord, wo, wi) * PI,
+            Ray::new(hitrecord.get_inpact_point(), onb.local_to_world(wi)),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+
+        self.f(hitrecord, wo, wi) * wi.z()
+    }
+
+    fn pdf(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        Some(sampling::cosine_hemisphere_pdf(
+            wi.unit_vector().dot(hitrecord.normal()),
+        ))
+    }
+}
+
+/// Thin diffuse sheet (e.g. paper, leaves or lamp shades) that scatters light diffusely to both
+/// sides. On closed objects the transmitted light enters the object, so it acts like a thin
+/// shell.
+pub struct DiffuseTransmission {
+    /// Diffusely reflected fraction
+    reflectance: Rc<dyn Texture>,
+    /// Diffusely transmitted fraction
+    transmittance: Rc<dyn Texture>,
+}
+
+impl DiffuseTransmission {
+    pub fn new(reflectance: Rc<dyn Texture>, transmittance: Rc<dyn Texture>) -> Self {
+        Self {
+            reflectance,
+            transmittance,
+        }
+    }
+}
+
+impl Material for DiffuseTransmission {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let (uv, p) = (hitrecord.uv(), hitrecord.get_inpact_point());
+        let reflectance = self.reflectance.value(uv, p);
+        let transmittance = self.transmittance.value(uv, p);
+
+        // choose the side with the luminance of the reflected and transmitted light
+        let (r, t) = (reflectance.luminance(), transmittance.luminance());
+        if r + t <= 0.0 {
+            return None;
+        }
+        let probability = r / (r + t);
+        let (normal, attenuation) = match sampler.get_1d() < probability {
+            true => (hitrecord.normal(), reflectance * (1.0 / probability)),
+            false => (
+                -hitrecord.normal(),
+                transmittance * (1.0 / (1.0 - probability)),
+            ),
+        };
+
+        let local = sampling::sample_cosine_hemisphere(sampler.get_2d());
+        Some((
+            attenuation,
+            Ray::new(p, Onb::from_w(normal).local_to_world(local)),
+        ))
+    }
+
+    fn eval(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let (uv, p) = (hitrecord.uv(), hitrecord.get_inpact_point());
+        let cos_theta = wi.unit_vector().dot(hitrecord.normal());
+        let fraction = match cos_theta > 0.0 {
+            true => self.reflectance.value(uv, p),
+            false => self.transmittance.value(uv, p),
+        };
+
+        fraction * (cos_theta.abs() / PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        let (uv, p) = (hitrecord.uv(), hitrecord.get_inpact_point());
+        let r = self.reflectance.value(uv, p).luminance();
+        let t = self.transmittance.value(uv, p).luminance();
+        if r + t <= 0.0 {
+            return Some(0.0);
+        }
+
+        let cos_theta = wi.unit_vector().dot(hitrecord.normal());
+        let probability = match cos_theta > 0.0 {
+            true => r / (r + t),
+            false => t / (r + t),
+        };
+        Some(probability * sampling::cosine_hemisphere_pdf(cos_theta.abs()))
+    }
 }
 
 pub struct Metal {
@@ -32,39 +302,206 @@ pub struct Metal {
     albedo: Color,
     /// Radius of the circle used to randomize ray bounce
     fuzz: f64,
+    /// Thin film on top of the metal
+    coating: Option<ThinFilm>,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
-        Self { albedo, fuzz }
+        Self {
+            albedo,
+            fuzz,
+            coating: None,
+        }
     }
-}
 
-impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, hitrecord: &HitRecord) -> Option<(Color, Ray)> {
-        let reflected = r_in.direction().unit_vector().reflect(&hitrecord.normal());
+    /// Covers the metal with a thin film, the reflectance then follows from the interference in
+    /// the film on a metal with the reflectance `albedo`.
+    pub fn coating(mut self, coating: ThinFilm) -> Self {
+        self.coating = Some(coating);
+        self
+    }
+
+    fn scatter_with_wavelengths(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Option<(Color, Ray)> {
+        let unit_dir = r_in.direction().unit_vector();
+        let reflected = unit_dir.reflect(&hitrecord.normal());
         let scattered = Ray::new(
             hitrecord.get_inpact_point(),
-            reflected + self.fuzz * Vec3::random_in_unit_sphere(),
+            reflected
+                + self.fuzz * sampling::sample_uniform_ball(sampler.get_2d(), sampler.get_1d()),
         );
 
-        if scattered.direction().dot(hitrecord.normal()) > 0.0 {
-            return Some((self.albedo, scattered));
+        if scattered.direction().dot(hitrecord.normal()) <= 0.0 {
+            return None;
         }
 
-        None
+        Some((self.reflectance(r_in, hitrecord, wavelengths), scattered))
+    }
+
+    /// Returns the fraction of the light that is reflected towards the origin of `r_in`, in RGB
+    /// or, with `wavelengths`, at the wavelengths of the path.
+    fn reflectance(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Color {
+        match (&self.coating, wavelengths) {
+            (Some(film), _) => film.reflectance(
+                hitrecord,
+                (-r_in.direction().unit_vector()).dot(hitrecord.normal()),
+                1.0,
+                thin_film::metal_ior(self.albedo),
+                wavelengths,
+            ),
+            (None, Some(wavelengths)) => wavelengths.upsample(self.albedo),
+            (None, None) => self.albedo,
+        }
+    }
+
+    /// Returns the density of the direction `wi` among the directions of `scatter`, which are
+    /// the reflected direction plus a uniform point in a ball with a radius of `fuzz`. It is the
+    /// density of the points in the ball along the ray from the hit point in direction `wi`.
+    fn fuzz_pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> f64 {
+        let wi = wi.unit_vector();
+        if wi.dot(hitrecord.normal()) <= 0.0 {
+            return 0.0;
+        }
+
+        // distances along `wi` where it enters and leaves the ball around the reflection
+        let reflected = r_in.direction().unit_vector().reflect(&hitrecord.normal());
+        let cos = wi.dot(reflected);
+        let discriminant = cos * cos - 1.0 + self.fuzz * self.fuzz;
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+        let t_far = cos + discriminant.sqrt();
+        let t_near = (cos - discriminant.sqrt()).max(0.0);
+        if t_far <= 0.0 {
+            return 0.0;
+        }
+
+        (t_far.powi(3) - t_near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+}
+
+impl Material for Metal {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, None)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, Some(wavelengths))
+    }
+
+    /// The attenuation of `scatter` does not depend on the direction, so the BSDF times the
+    /// cosine is the reflectance times the density of the direction. A metal without `fuzz` is a
+    /// mirror, which only reflects into a single direction.
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        if self.fuzz <= 0.0 {
+            return Color::default();
+        }
+        self.reflectance(r_in, hitrecord, None) * self.fuzz_pdf(r_in, hitrecord, wi)
+    }
+
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        if self.fuzz <= 0.0 {
+            return Color::default();
+        }
+        self.reflectance(r_in, hitrecord, Some(wavelengths)) * self.fuzz_pdf(r_in, hitrecord, wi)
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        match self.fuzz > 0.0 {
+            true => Some(self.fuzz_pdf(r_in, hitrecord, wi)),
+            false => None,
+        }
     }
 }
 
+/// Smooth dielectric (e.g. glass or water), that both reflects and refracts light.
+///
+/// Colored glass absorbs light inside the object following the Beer-Lambert law, see
+/// `RoughDielectric`.
 pub struct Dielectric {
     /// Refraction index of the dielectric material
-    ref_index: f64,
+    ior: Ior,
+    /// Thin film on top of the dielectric
+    coating: Option<ThinFilm>,
+    /// Fraction of the light absorbed per unit of distance inside the material, per color channel
+    absorption: Color,
+    /// Color that filters the light refracted into the material
+    tint: Color,
 }
 
 impl Dielectric {
     pub fn new(ref_index: f64) -> Self {
-        assert!(ref_index >= 1.0, "refraction index cannot be lower than 1");
-        Self { ref_index }
+        Self::with_ior(Ior::Constant(ref_index))
+    }
+
+    /// Returns a dielectric with a (possibly wavelength dependent) refraction index. Dispersion is
+    /// only visible in spectral mode, RGB renders use the index at the d line.
+    pub fn with_ior(ior: Ior) -> Self {
+        assert!(
+            ior.d_line() >= 1.0,
+            "refraction index cannot be lower than 1"
+        );
+        Self {
+            ior,
+            coating: None,
+            absorption: Color::default(),
+            tint: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Covers the dielectric with a thin film, e.g. `Dielectric::new(1.0)` with a soap film is a
+    /// soap bubble.
+    pub fn coating(mut self, coating: ThinFilm) -> Self {
+        self.coating = Some(coating);
+        self
+    }
+
+    /// Sets the absorption coefficient of the medium inside, see `RoughDielectric::absorption`.
+    pub fn absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Sets the absorption from the color that is left after `distance`, see
+    /// `RoughDielectric::transmittance_at_distance`.
+    pub fn transmittance_at_distance(mut self, transmittance: Color, distance: f64) -> Self {
+        self.absorption = absorption_for_transmittance(transmittance, distance);
+        self
+    }
+
+    /// Sets the color that filters the light refracted into the material, independent of the
+    /// thickness of the object.
+    pub fn tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
     }
 
     /// Returns the ratio that should be reflected using the `Schlick's approximation` (<https://en.wikipedia.org/wiki/Schlick%27s_approximation>)
@@ -72,36 +509,1647 @@ impl Dielectric {
         let r0 = ((1.0 - ref_index) / (1.0 + ref_index)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
-}
 
-impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, hitrecord: &HitRecord) -> Option<(Color, Ray)> {
+    /// Scatters the ray at a dielectric with refraction index `ref_index`. The attenuation is in
+    /// RGB or, with `wavelengths`, at the wavelengths of the path.
+    fn scatter_with_ref_index(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        ref_index: f64,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Option<(Color, Ray)> {
         // calculate eta_over_etap according to hitting a frontface
         let ref_ratio = if hitrecord.hit_frontface() {
-            1.0 / self.ref_index
+            1.0 / ref_index
         } else {
-            self.ref_index
+            ref_index
         };
 
         let unit_dir = r_in.direction().unit_vector();
 
         let cos_theta = (-unit_dir).dot(hitrecord.normal());
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+        let white = Color::new(1.0, 1.0, 1.0);
 
         // check whether to refract or reflect
-        let direction = if ref_ratio * sin_theta > 1.0
-            || Self::reflectance(cos_theta, ref_ratio) > utils::random_double()
-        {
-            // reflect
-            unit_dir.reflect(&hitrecord.normal())
-        } else {
-            // refract
-            unit_dir.refract(&hitrecord.normal(), ref_ratio)
+        let (reflect, attenuation) = match &self.coating {
+            _ if ref_ratio * sin_theta > 1.0 => (true, white),
+            None => (
+                Self::reflectance(cos_theta, ref_ratio) > sampler.get_1d(),
+                white,
+            ),
+            Some(film) => {
+                let (incident_ior, substrate_ior) = match hitrecord.hit_frontface() {
+                    true => (1.0, ref_index),
+                    false => (ref_index, 1.0),
+                };
+                let reflectance = film.reflectance(
+                    hitrecord,
+                    cos_theta,
+                    incident_ior,
+                    |_| (substrate_ior, 0.0),
+                    wavelengths,
+                );
+
+                // choose with the mean reflectance and weight the (colored) result accordingly
+                let probability = (reflectance.red() + reflectance.green() + reflectance.blue())
+                    .clamp(1.0e-3, 3.0 - 3.0e-3)
+                    / 3.0;
+                match sampler.get_1d() < probability {
+                    true => (true, reflectance * (1.0 / probability)),
+                    false => {
+                        let transmittance = Color::new(
+                            1.0 - reflectance.red(),
+                            1.0 - reflectance.green(),
+                            1.0 - reflectance.blue(),
+                        );
+                        (false, transmittance * (1.0 / (1.0 - probability)))
+                    }
+                }
+            }
+        };
+
+        let direction = match reflect {
+            true => unit_dir.reflect(&hitrecord.normal()),
+            false => unit_dir.refract(&hitrecord.normal(), ref_ratio),
+        };
+
+        let medium = medium_attenuation(r_in, hitrecord, !reflect, self.tint, self.absorption);
+        let medium = match wavelengths {
+            Some(wavelengths) => wavelengths.upsample(medium),
+            None => medium,
         };
 
         Some((
-            Color::new(1.0, 1.0, 1.0),
+            attenuation * medium,
             Ray::new(hitrecord.get_inpact_point(), direction),
         ))
     }
 }
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_ref_index(r_in, hitrecord, sampler, self.ior.d_line(), None)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        // with dispersion every wavelength takes its own direction, only the hero one is followed
+        let ref_index = match self.ior.dispersive() {
+            true => {
+                wavelengths.terminate_secondary();
+                self.ior.at(wavelengths.hero())
+            }
+            false => self.ior.d_line(),
+        };
+
+        self.scatter_with_ref_index(r_in, hitrecord, sampler, ref_index, Some(wavelengths))
+    }
+}
+
+/// Rough metal based on the GGX microfacet distribution (Cook-Torrance model). The reflectance
+/// follows from the complex refraction index `eta + i k` of the metal, per color channel.
+pub struct MicrofacetConductor {
+    eta: Vec3,
+    k: Vec3,
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetConductor {
+    /// Returns a new instance of the `MicrofacetConductor` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `eta`: Real part of the refraction index for the red, green and blue channel.
+    /// * `k`: Imaginary part (absorption coefficient) of the refraction index for every channel.
+    /// * `roughness`: Perceptual roughness in `[0, 1]`, `0` results in a perfect mirror.
+    pub fn new(eta: Vec3, k: Vec3, roughness: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(
+            Vec3::new(0.155, 0.117, 0.138),
+            Vec3::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_i: f64) -> Color {
+        Color::new(
+            microfacet::fresnel_conductor(cos_i, self.eta.x(), self.k.x()),
+            microfacet::fresnel_conductor(cos_i, self.eta.y(), self.k.y()),
+            microfacet::fresnel_conductor(cos_i, self.eta.z(), self.k.z()),
+        )
+    }
+}
+
+impl Material for MicrofacetConductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.effectively_smooth() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            return Some((
+                self.fresnel(wo.z()),
+                Ray::new(hitrecord.get_inpact_point(), onb.local_to_world(wi)),
+            ));
+        }
+
+        // sample a visible microfacet and reflect around it
+        let wm = self.distribution.sample_wm(wo, sampler.get_2d());
+        let wi = microfacet::reflect(wo, wm);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // the distribution and cosine terms cancel out against the sampling density
+        let attenuation =
+            self.fresnel(wo.dot(wm)) * (self.distribution.g(wo, wi) / self.distribution.g1(wo));
+
+        Some((
+            attenuation,
+            Ray::new(hitrecord.get_inpact_point(), onb.local_to_world(wi)),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        if self.distribution.effectively_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+
+        let wm = (wo + wi).unit_vector();
+        self.fresnel(wo.dot(wm))
+            * (self.distribution.d(wm) * self.distribution.g(wo, wi) / (4.0 * wo.z()))
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        if self.distribution.effectively_smooth() {
+            return None;
+        }
+
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Some(0.0);
+        }
+
+        let wm = (wo + wi).unit_vector();
+        Some(self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(wm)))
+    }
+}
+
+/// Dielectric (e.g. glass or water) with a rough surface based on the GGX microfacet
+/// distribution, that both reflects and refracts light.
+///
+/// Colored glass absorbs light inside the object following the Beer-Lambert law. The distance a
+/// ray traveled inside is the length of the segment ending at a hit on the inside of the surface,
+/// so objects with an absorbing medium should be closed and not overlap other objects.
+pub struct RoughDielectric {
+    /// Refraction index of the dielectric material
+    ior: Ior,
+    distribution: TrowbridgeReitz,
+    /// Fraction of the light absorbed per unit of distance inside the material, per color channel
+    absorption: Color,
+    /// Color that filters the light refracted into the material
+    tint: Color,
+}
+
+impl RoughDielectric {
+    /// Returns a new instance of the `RoughDielectric` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `ref_index`: Refraction index of the material.
+    /// * `roughness`: Perceptual roughness in `[0, 1]`, `0` results in smooth glass.
+    pub fn new(ref_index: f64, roughness: f64) -> Self {
+        assert!(ref_index >= 1.0, "refraction index cannot be lower than 1");
+        Self {
+            ior: Ior::Constant(ref_index),
+            distribution: TrowbridgeReitz::from_roughness(roughness),
+            absorption: Color::default(),
+            tint: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Replaces the refraction index by a wavelength dependent one. Dispersion is only visible in
+    /// spectral mode, RGB renders use the index at the d line.
+    pub fn dispersion(mut self, ior: Ior) -> Self {
+        assert!(
+            ior.d_line() >= 1.0,
+            "refraction index cannot be lower than 1"
+        );
+        self.ior = ior;
+        self
+    }
+
+    /// Sets the absorption coefficient of the medium inside, light that travels a distance `d`
+    /// through it is attenuated by `exp(-absorption * d)`.
+    pub fn absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Sets the absorption so light that travels `distance` through the medium keeps the
+    /// `transmittance` color, which is easier to pick than the absorption coefficient. A channel
+    /// with a transmittance of `0` is absorbed completely.
+    pub fn transmittance_at_distance(mut self, transmittance: Color, distance: f64) -> Self {
+        self.absorption = absorption_for_transmittance(transmittance, distance);
+        self
+    }
+
+    /// Sets the color that filters the light refracted into the material, independent of the
+    /// thickness of the object.
+    pub fn tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+}
+
+impl RoughDielectric {
+    fn scatter_with_ref_index(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        ref_index: f64,
+    ) -> Option<(Color, Ray)> {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let eta = Self::relative_ior(hitrecord, ref_index);
+        let (wi, attenuation) = sample_dielectric_interface(wo, eta, &self.distribution, sampler)?;
+        let attenuation = attenuation * self.medium_attenuation(r_in, hitrecord, wi.z() < 0.0);
+
+        Some((
+            attenuation,
+            Ray::new(hitrecord.get_inpact_point(), onb.local_to_world(wi)),
+        ))
+    }
+
+    /// Returns the refraction index of the side a ray at `hitrecord` goes to, relative to the
+    /// side it comes from.
+    fn relative_ior(hitrecord: &HitRecord, ref_index: f64) -> f64 {
+        match hitrecord.hit_frontface() {
+            true => ref_index,
+            false => 1.0 / ref_index,
+        }
+    }
+
+    /// Returns the attenuation by the tint of light that is refracted into the material and by
+    /// the absorption of the medium that `r_in` travelled through.
+    fn medium_attenuation(&self, r_in: &Ray, hitrecord: &HitRecord, refracted: bool) -> Color {
+        medium_attenuation(r_in, hitrecord, refracted, self.tint, self.absorption)
+    }
+
+    /// Returns the BSDF times the cosine and the density of `scatter` for the direction `wi`,
+    /// without the attenuation of the medium, or `None` for smooth surfaces.
+    fn eval_with_ref_index(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        ref_index: f64,
+    ) -> Option<(f64, f64)> {
+        if self.distribution.effectively_smooth() {
+            return None;
+        }
+
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        let eta = Self::relative_ior(hitrecord, ref_index);
+        Some(eval_dielectric_interface(wo, wi, eta, &self.distribution))
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_ref_index(r_in, hitrecord, sampler, self.ior.d_line())
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        // with dispersion every wavelength takes its own direction, only the hero one is followed
+        let ref_index = match self.ior.dispersive() {
+            true => {
+                wavelengths.terminate_secondary();
+                self.ior.at(wavelengths.hero())
+            }
+            false => self.ior.d_line(),
+        };
+
+        let (attenuation, scattered) =
+            self.scatter_with_ref_index(r_in, hitrecord, sampler, ref_index)?;
+        Some((wavelengths.upsample(attenuation), scattered))
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let Some((f, _)) = self.eval_with_ref_index(r_in, hitrecord, wi, self.ior.d_line()) else {
+            return Color::default();
+        };
+        let refracted = wi.dot(hitrecord.normal()) < 0.0;
+        f * self.medium_attenuation(r_in, hitrecord, refracted)
+    }
+
+    /// With dispersion every wavelength is evaluated with its own refraction index, as the
+    /// direction is given.
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        if !self.ior.dispersive() {
+            return wavelengths.upsample(self.eval(r_in, hitrecord, wi));
+        }
+
+        let f = wavelengths.lambda().map(|lambda| {
+            self.eval_with_ref_index(r_in, hitrecord, wi, self.ior.at(lambda))
+                .map_or(0.0, |(f, _)| f)
+        });
+        let refracted = wi.dot(hitrecord.normal()) < 0.0;
+        Color::new(f[0], f[1], f[2])
+            * wavelengths.upsample(self.medium_attenuation(r_in, hitrecord, refracted))
+    }
+
+    /// Returns the density at the refraction index of the d line, which is also used for the
+    /// paths with dispersion. Smooth surfaces only scatter into discrete directions.
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        self.eval_with_ref_index(r_in, hitrecord, wi, self.ior.d_line())
+            .map(|(_, pdf)| pdf)
+    }
+}
+
+/// Returns the absorption coefficient of a medium in which light that travels `distance` keeps
+/// the `transmittance` color. Channels with a transmittance of `0` have an infinite absorption.
+fn absorption_for_transmittance(transmittance: Color, distance: f64) -> Color {
+    assert!(distance > 0.0, "distance should be larger than 0");
+    let absorption = |t: f64| {
+        assert!(
+            (0.0..=1.0).contains(&t),
+            "transmittance should be in [0, 1]"
+        );
+        match t > 0.0 {
+            true => -t.ln() / distance,
+            false => f64::INFINITY,
+        }
+    };
+
+    Color::new(
+        absorption(transmittance.red()),
+        absorption(transmittance.green()),
+        absorption(transmittance.blue()),
+    )
+}
+
+/// Returns the attenuation of a ray that scatters at a dielectric by the `tint` of light that is
+/// `refracted` into the material and by the `absorption` of the medium inside, for the distance
+/// `r_in` traveled through it.
+fn medium_attenuation(
+    r_in: &Ray,
+    hitrecord: &HitRecord,
+    refracted: bool,
+    tint: Color,
+    absorption: Color,
+) -> Color {
+    let mut attenuation = Color::new(1.0, 1.0, 1.0);
+
+    if hitrecord.hit_frontface() && refracted {
+        attenuation *= tint;
+    }
+    if !hitrecord.hit_frontface() {
+        // the ray traveled through the medium from its origin to the hit point
+        let distance = hitrecord.get_t() * r_in.direction().length();
+        let transmittance = |absorption: f64| match absorption.is_finite() {
+            true => (-absorption * distance).exp(),
+            false => 0.0,
+        };
+        attenuation *= Color::new(
+            transmittance(absorption.red()),
+            transmittance(absorption.green()),
+            transmittance(absorption.blue()),
+        );
+    }
+
+    attenuation
+}
+
+/// Samples the direction of light that is reflected or refracted by a (rough) dielectric
+/// interface, where `wo` is in the local shading space. Returns the direction and the throughput
+/// weight, or `None` if the sample is lost.
+///
+/// Arguments:
+///
+/// * `eta`: Refraction index of the side the light goes to, relative to the side of `wo`.
+fn sample_dielectric_interface(
+    wo: Vec3,
+    eta: f64,
+    distribution: &TrowbridgeReitz,
+    sampler: &mut dyn Sampler,
+) -> Option<(Vec3, f64)> {
+    // smooth surfaces only have the macro surface normal
+    let smooth = distribution.effectively_smooth();
+    let wm = match smooth {
+        true => Vec3::new(0.0, 0.0, 1.0),
+        false => distribution.sample_wm(wo, sampler.get_2d()),
+    };
+
+    // choose between reflection and refraction according to the Fresnel reflectance, which
+    // cancels the Fresnel term in the attenuation
+    let reflectance = microfacet::fresnel_dielectric(wo.dot(wm), eta);
+    let wi = match sampler.get_1d() < reflectance {
+        true => microfacet::reflect(wo, wm),
+        false => microfacet::refract(wo, wm, eta)?,
+    };
+
+    // reflections should stay above and refractions below the macro surface
+    let reflected = wo.dot(wm) * wi.dot(wm) > 0.0;
+    if reflected != (wi.z() > 0.0) {
+        return None;
+    }
+
+    let attenuation = match smooth {
+        true => 1.0,
+        false => distribution.g(wo, wi) / distribution.g1(wo),
+    };
+
+    Some((wi, attenuation))
+}
+
+/// Returns the BSDF times the absolute cosine of `wi` and the density of
+/// `sample_dielectric_interface` for light from `wi` that a rough dielectric interface reflects or
+/// refracts towards `wo`, both in the local shading space.
+///
+/// Arguments:
+///
+/// * `eta`: Refraction index of the side the light goes to, relative to the side of `wo`.
+fn eval_dielectric_interface(
+    wo: Vec3,
+    wi: Vec3,
+    eta: f64,
+    distribution: &TrowbridgeReitz,
+) -> (f64, f64) {
+    if wo.z() <= 0.0 || wi.z() == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    // the microfacet normal that reflects or refracts `wo` into `wi`
+    let reflected = wi.z() > 0.0;
+    let wm = match reflected {
+        true => wo + wi,
+        false => eta * wi + wo,
+    };
+    if wm.near_zero() {
+        return (0.0, 0.0);
+    }
+    let wm = wm.unit_vector();
+    let wm = if wm.z() < 0.0 { -wm } else { wm };
+
+    // both directions have to be on the same side of the microfacet as of the macro surface
+    if wm.dot(wi) * wi.z() <= 0.0 || wm.dot(wo) <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let reflectance = microfacet::fresnel_dielectric(wo.dot(wm), eta);
+    let d_g = distribution.d(wm) * distribution.g(wo, wi);
+    match reflected {
+        true => (
+            d_g * reflectance / (4.0 * wo.z()),
+            distribution.d_visible(wo, wm) * reflectance / (4.0 * wo.dot(wm)),
+        ),
+        false => {
+            let denominator = (wi.dot(wm) + wo.dot(wm) / eta).powi(2);
+            let transmittance = 1.0 - reflectance;
+            (
+                d_g * transmittance * (wi.dot(wm) * wo.dot(wm)).abs() / (wo.z() * denominator),
+                distribution.d_visible(wo, wm) * transmittance * wi.dot(wm).abs() / denominator,
+            )
+        }
+    }
+}
+
+/// Smallest `alpha` of the microfacet lobes of the `PrincipledBsdf`, smoother surfaces would need
+/// to be handled as perfect mirrors.
+const MIN_PRINCIPLED_ALPHA: f64 = 1.0e-3;
+
+/// `alpha` of the distribution used for the masking-shadowing of the clearcoat lobe.
+const CLEARCOAT_MASKING_ALPHA: f64 = 0.25;
+
+/// Artist friendly "uber" material after the Disney principled BSDF (Burley, "Physically Based
+/// Shading at Disney" and "Extending the Disney BRDF to a BSDF with Integrated Subsurface
+/// Scattering"). A single set of parameters in `[0, 1]` covers plastics, metals, glass and
+/// everything in between.
+///
+/// The BSDF consists of a diffuse lobe with retro-reflection and sheen, a GGX specular lobe, a
+/// GTR1 clearcoat lobe and a GGX transmission lobe. A scattered direction is sampled from one of
+/// the lobes, chosen according to their estimated contribution, and weighted by the density of
+/// all lobes together.
+///
+/// Every parameter except the refraction index can be given as texture. Parameters that are not
+/// set keep their default value (see `new`).
+pub struct PrincipledBsdf {
+    base_color: Rc<dyn Texture>,
+    metallic: Rc<dyn FloatTexture>,
+    roughness: Rc<dyn FloatTexture>,
+    specular: Rc<dyn FloatTexture>,
+    specular_tint: Rc<dyn FloatTexture>,
+    sheen: Rc<dyn FloatTexture>,
+    clearcoat: Rc<dyn FloatTexture>,
+    clearcoat_gloss: Rc<dyn FloatTexture>,
+    transmission: Rc<dyn FloatTexture>,
+    ior: f64,
+}
+
+impl PrincipledBsdf {
+    /// Returns a dielectric with the diffuse color `base_color`, a roughness of `0.5`, a specular
+    /// of `0.5`, a clearcoat gloss of `1`, a refraction index of `1.5` and all other parameters
+    /// set to `0`.
+    pub fn new(base_color: Rc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: Rc::new(0.0),
+            roughness: Rc::new(0.5),
+            specular: Rc::new(0.5),
+            specular_tint: Rc::new(0.0),
+            sheen: Rc::new(0.0),
+            clearcoat: Rc::new(0.0),
+            clearcoat_gloss: Rc::new(1.0),
+            transmission: Rc::new(0.0),
+            ior: 1.5,
+        }
+    }
+
+    /// Blends from a dielectric (`0`) to a metal (`1`) that reflects with the base color.
+    pub fn metallic(mut self, metallic: Rc<dyn FloatTexture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    /// Perceptual roughness of the specular and transmission lobes, which is squared to get the
+    /// `alpha` of the microfacet distribution.
+    pub fn roughness(mut self, roughness: Rc<dyn FloatTexture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Specular reflectance of the dielectric part at normal incidence, scaled so `0.5` reflects
+    /// 4% (a refraction index of 1.5) and `1` reflects 8%.
+    pub fn specular(mut self, specular: Rc<dyn FloatTexture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    /// Tints the specular reflection of the dielectric part towards the hue of the base color.
+    pub fn specular_tint(mut self, specular_tint: Rc<dyn FloatTexture>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    /// Strength of the white retro-reflective sheen at grazing angles, e.g. for cloth.
+    pub fn sheen(mut self, sheen: Rc<dyn FloatTexture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    /// Strength of a second, white specular lobe on top of the material, e.g. for car paint.
+    pub fn clearcoat(mut self, clearcoat: Rc<dyn FloatTexture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    /// Glossiness of the clearcoat, from satin (`0`) to glossy (`1`).
+    pub fn clearcoat_gloss(mut self, clearcoat_gloss: Rc<dyn FloatTexture>) -> Self {
+        self.clearcoat_gloss = clearcoat_gloss;
+        self
+    }
+
+    /// Blends the dielectric part from opaque (`0`) to transparent (`1`), the transmitted light is
+    /// tinted by the base color.
+    pub fn transmission(mut self, transmission: Rc<dyn FloatTexture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    /// Refraction index used for the refraction of the transmission lobe.
+    pub fn ior(mut self, ior: f64) -> Self {
+        assert!(ior >= 1.0, "refraction index cannot be lower than 1");
+        self.ior = ior;
+        self
+    }
+}
+
+impl PrincipledBsdf {
+    /// Returns whether `hitrecord` is a ray leaving a transparent object, which only passes the
+    /// interface back to the outside.
+    fn leaves_transparent(&self, hitrecord: &HitRecord) -> bool {
+        !hitrecord.hit_frontface()
+            && self
+                .transmission
+                .value(hitrecord.uv(), hitrecord.get_inpact_point())
+                > 0.0
+    }
+
+    /// Returns the roughness at `hitrecord` and the distribution of the specular and
+    /// transmission lobes that follows from it.
+    fn roughness_at(&self, hitrecord: &HitRecord) -> (f64, TrowbridgeReitz) {
+        let roughness = self
+            .roughness
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+        let alpha = (roughness * roughness).max(MIN_PRINCIPLED_ALPHA);
+        (roughness, TrowbridgeReitz::new(alpha, alpha))
+    }
+
+    /// Returns the lobes at `hitrecord` seen from `wo` (in the local shading space).
+    fn lobes(&self, hitrecord: &HitRecord, wo: Vec3) -> Option<PrincipledLobes> {
+        let (uv, p) = (hitrecord.uv(), hitrecord.get_inpact_point());
+        let parameter = |texture: &Rc<dyn FloatTexture>| texture.value(uv, p).clamp(0.0, 1.0);
+        let (roughness, distribution) = self.roughness_at(hitrecord);
+
+        PrincipledLobes::new(
+            self.base_color.value(uv, p),
+            parameter(&self.metallic),
+            roughness,
+            distribution,
+            parameter(&self.specular),
+            parameter(&self.specular_tint),
+            parameter(&self.sheen),
+            parameter(&self.clearcoat),
+            parameter(&self.clearcoat_gloss),
+            parameter(&self.transmission),
+            self.ior,
+            wo,
+        )
+    }
+}
+
+impl Material for PrincipledBsdf {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let p = hitrecord.get_inpact_point();
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.leaves_transparent(hitrecord) {
+            let (_, distribution) = self.roughness_at(hitrecord);
+            let (wi, attenuation) =
+                sample_dielectric_interface(wo, 1.0 / self.ior, &distribution, sampler)?;
+            return Some((
+                Color::new(attenuation, attenuation, attenuation),
+                Ray::new(p, onb.local_to_world(wi)),
+            ));
+        }
+
+        let lobes = self.lobes(hitrecord, wo)?;
+        let wi = lobes.sample_wi(wo, sampler.get_1d(), sampler.get_2d())?;
+        let pdf = lobes.pdf(wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some((
+            lobes.f(wo, wi) * (wi.z().abs() / pdf),
+            Ray::new(p, onb.local_to_world(wi)),
+        ))
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+        if wo.z() <= 0.0 || self.leaves_transparent(hitrecord) {
+            return Color::default();
+        }
+
+        match self.lobes(hitrecord, wo) {
+            Some(lobes) => lobes.f(wo, wi) * wi.z().abs(),
+            None => Color::default(),
+        }
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        let onb = Onb::from_w(hitrecord.normal());
+        let wo = onb.world_to_local(-r_in.direction().unit_vector());
+        let wi = onb.world_to_local(wi.unit_vector());
+
+        // leaving a transparent object is not included in `eval`
+        if self.leaves_transparent(hitrecord) {
+            return None;
+        }
+        if wo.z() <= 0.0 {
+            return Some(0.0);
+        }
+
+        Some(
+            self.lobes(hitrecord, wo)
+                .map_or(0.0, |lobes| lobes.pdf(wo, wi)),
+        )
+    }
+}
+
+/// The lobes of a `PrincipledBsdf` with the parameters of a single hit point. All directions are
+/// in the local shading space.
+struct PrincipledLobes {
+    base_color: Color,
+    roughness: f64,
+    distribution: TrowbridgeReitz,
+    /// Reflectance of the specular lobe at normal incidence
+    specular_color: Color,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    /// Weight of the diffuse and sheen lobes
+    diffuse_weight: f64,
+    /// Weight of the transmission lobe
+    transmission_weight: f64,
+    eta: f64,
+    /// Probabilities of sampling the diffuse, specular, clearcoat and transmission lobe
+    lobe_probabilities: [f64; 4],
+}
+
+impl PrincipledLobes {
+    /// Returns the lobes seen from `wo`, or `None` if the surface does not scatter any light.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        base_color: Color,
+        metallic: f64,
+        roughness: f64,
+        distribution: TrowbridgeReitz,
+        specular: f64,
+        specular_tint: f64,
+        sheen: f64,
+        clearcoat: f64,
+        clearcoat_gloss: f64,
+        transmission: f64,
+        eta: f64,
+        wo: Vec3,
+    ) -> Option<Self> {
+        let luminance = base_color.luminance();
+        let tint = match luminance > 0.0 {
+            true => base_color * (1.0 / luminance),
+            false => Color::new(1.0, 1.0, 1.0),
+        };
+        let dielectric_specular =
+            (0.08 * specular) * lerp(Color::new(1.0, 1.0, 1.0), tint, specular_tint);
+        let specular_color = lerp(dielectric_specular, base_color, metallic);
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let transmission_weight = (1.0 - metallic) * transmission;
+
+        // estimate the contribution of every lobe from the reflectance towards `wo`
+        let mut lobe_probabilities = [
+            diffuse_weight * (luminance + sheen),
+            schlick(specular_color, wo.z()).luminance().max(1.0e-3),
+            0.25 * clearcoat * schlick_f64(0.04, wo.z()),
+            transmission_weight * luminance * (1.0 - microfacet::fresnel_dielectric(wo.z(), eta)),
+        ];
+        let total: f64 = lobe_probabilities.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        lobe_probabilities.iter_mut().for_each(|p| *p /= total);
+
+        Some(Self {
+            base_color,
+            roughness,
+            distribution,
+            specular_color,
+            sheen,
+            clearcoat,
+            clearcoat_alpha: lerp_f64(0.1, 0.001, clearcoat_gloss),
+            diffuse_weight,
+            transmission_weight,
+            eta,
+            lobe_probabilities,
+        })
+    }
+
+    /// Samples a direction from one of the lobes, chosen with `lobe_u`. Returns `None` if the
+    /// direction is on the wrong side of the surface for the lobe.
+    fn sample_wi(&self, wo: Vec3, lobe_u: f64, u: (f64, f64)) -> Option<Vec3> {
+        let mut cdf = 0.0;
+        let lobe = self
+            .lobe_probabilities
+            .iter()
+            .position(|p| {
+                cdf += p;
+                lobe_u < cdf
+            })
+            .unwrap_or(3);
+
+        let wi = match lobe {
+            0 => sampling::sample_cosine_hemisphere(u),
+            1 => microfacet::reflect(wo, self.distribution.sample_wm(wo, u)),
+            2 => {
+                // sample the clearcoat normal proportional to `D(wm) cos(theta_m)`
+                let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+                let cos_theta = ((1.0 - a2.powf(1.0 - u.0)) / (1.0 - a2)).max(0.0).sqrt();
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * u.1;
+                let wm = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                microfacet::reflect(wo, wm)
+            }
+            _ => microfacet::refract(wo, self.distribution.sample_wm(wo, u), self.eta)?,
+        };
+
+        match (lobe == 3) == (wi.z() < 0.0) {
+            true => Some(wi),
+            false => None,
+        }
+    }
+
+    /// Returns the value of the BSDF for light arriving from `wi` that scatters towards `wo`.
+    fn f(&self, wo: Vec3, wi: Vec3) -> Color {
+        // the cosines divide the specular and clearcoat lobes
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return Color::default();
+        }
+        if wi.z() < 0.0 {
+            return self.f_transmission(wo, wi);
+        }
+
+        let wh = (wo + wi).unit_vector();
+        let cos_d = wi.dot(wh);
+        let mut f = Color::default();
+
+        if self.diffuse_weight > 0.0 {
+            // diffuse with retro-reflection at grazing angles for rough surfaces
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let retro = (1.0 + (fd90 - 1.0) * (1.0 - wi.z()).powi(5))
+                * (1.0 + (fd90 - 1.0) * (1.0 - wo.z()).powi(5));
+            f += (self.diffuse_weight * retro / PI) * self.base_color;
+
+            let sheen = self.diffuse_weight * self.sheen * (1.0 - cos_d).powi(5);
+            f += Color::new(sheen, sheen, sheen);
+        }
+
+        let cosines = 4.0 * wo.z() * wi.z();
+        f += (self.distribution.d(wh) * self.distribution.g(wo, wi) / cosines)
+            * schlick(self.specular_color, cos_d);
+
+        if self.clearcoat > 0.0 {
+            let masking = TrowbridgeReitz::new(CLEARCOAT_MASKING_ALPHA, CLEARCOAT_MASKING_ALPHA);
+            let clearcoat = 0.25
+                * self.clearcoat
+                * gtr1(wh.z(), self.clearcoat_alpha)
+                * masking.g1(wo)
+                * masking.g1(wi)
+                * schlick_f64(0.04, cos_d)
+                / cosines;
+            f += Color::new(clearcoat, clearcoat, clearcoat);
+        }
+
+        f
+    }
+
+    fn f_transmission(&self, wo: Vec3, wi: Vec3) -> Color {
+        let Some((wm, denominator)) = self.refraction_half_vector(wo, wi) else {
+            return Color::default();
+        };
+
+        let transmittance = 1.0 - microfacet::fresnel_dielectric(wo.dot(wm), self.eta);
+        let value = self.transmission_weight
+            * self.distribution.d(wm)
+            * self.distribution.g(wo, wi)
+            * transmittance
+            * (wi.dot(wm) * wo.dot(wm) / (wi.z() * wo.z() * denominator)).abs();
+
+        value * self.base_color
+    }
+
+    /// Returns the density of sampling `wi` with `sample_wi`.
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = self.lobe_probabilities;
+
+        if wi.z() < 0.0 {
+            return match self.refraction_half_vector(wo, wi) {
+                Some((wm, denominator)) => {
+                    p_transmission * self.distribution.d_visible(wo, wm) * wi.dot(wm).abs()
+                        / denominator
+                }
+                None => 0.0,
+            };
+        }
+
+        let wh = (wo + wi).unit_vector();
+        let reflection_jacobian = 4.0 * wo.dot(wh);
+
+        p_diffuse * sampling::cosine_hemisphere_pdf(wi.z())
+            + p_specular * self.distribution.d_visible(wo, wh) / reflection_jacobian
+            + p_clearcoat * gtr1(wh.z(), self.clearcoat_alpha) * wh.z() / reflection_jacobian
+    }
+
+    /// Returns the microfacet normal that refracts `wo` into `wi` and the denominator of the
+    /// Jacobian of the refraction, or `None` if no microfacet facing `wo` does so.
+    fn refraction_half_vector(&self, wo: Vec3, wi: Vec3) -> Option<(Vec3, f64)> {
+        let wm = (self.eta * wi + wo).unit_vector();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+        if wm.dot(wi) >= 0.0 || wm.dot(wo) <= 0.0 {
+            return None;
+        }
+
+        Some((wm, (wi.dot(wm) + wo.dot(wm) / self.eta).powi(2)))
+    }
+}
+
+/// Generalized Trowbridge-Reitz distribution with `gamma = 1`, which has longer tails than GGX.
+fn gtr1(cos_theta: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_theta * cos_theta))
+}
+
+/// Schlick's approximation of the Fresnel reflectance for a reflectance of `f0` at normal
+/// incidence.
+fn schlick(f0: Color, cos: f64) -> Color {
+    let w = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    (1.0 - w) * f0 + Color::new(w, w, w)
+}
+
+fn schlick_f64(f0: f64, cos: f64) -> f64 {
+    lerp_f64(f0, 1.0, (1.0 - cos.clamp(0.0, 1.0)).powi(5))
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+fn lerp_f64(a: f64, b: f64, t: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+/// Blends two materials: every scattering event uses `second` with probability `weight` and
+/// `first` otherwise, which on average gives the weighted sum of both materials.
+pub struct MixMaterial {
+    first: Rc<dyn Material>,
+    second: Rc<dyn Material>,
+    /// Fraction of `second` in `[0, 1]`
+    weight: Rc<dyn FloatTexture>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        first: Rc<dyn Material>,
+        second: Rc<dyn Material>,
+        weight: Rc<dyn FloatTexture>,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            weight,
+        }
+    }
+
+    /// Returns the material used for the next scattering event at `hitrecord`.
+    fn choose(&self, hitrecord: &HitRecord, sampler: &mut dyn Sampler) -> &dyn Material {
+        let weight = self
+            .weight
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+
+        match sampler.get_1d() < weight {
+            true => self.second.as_ref(),
+            false => self.first.as_ref(),
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.choose(hitrecord, sampler)
+            .scatter(r_in, hitrecord, sampler)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        self.choose(hitrecord, sampler)
+            .scatter_spectral(r_in, hitrecord, sampler, wavelengths)
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let weight = self
+            .weight
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+
+        (1.0 - weight) * self.first.eval(r_in, hitrecord, wi)
+            + weight * self.second.eval(r_in, hitrecord, wi)
+    }
+
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        let weight = self
+            .weight
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+
+        (1.0 - weight) * self.first.eval_spectral(r_in, hitrecord, wi, wavelengths)
+            + weight * self.second.eval_spectral(r_in, hitrecord, wi, wavelengths)
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        let weight = self
+            .weight
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+
+        Some(
+            (1.0 - weight) * self.first.pdf(r_in, hitrecord, wi)?
+                + weight * self.second.pdf(r_in, hitrecord, wi)?,
+        )
+    }
+
+    fn emitted(&self, r_in: &Ray, hitrecord: &HitRecord) -> Color {
+        let weight = self
+            .weight
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .clamp(0.0, 1.0);
+
+        (1.0 - weight) * self.first.emitted(r_in, hitrecord)
+            + weight * self.second.emitted(r_in, hitrecord)
+    }
+}
+
+/// Maximal number of times light can pass through the coating of a `LayeredMaterial` before
+/// it is dropped.
+const MAX_LAYER_BOUNCES: usize = 32;
+
+/// Smooth, clear coating (e.g. varnish or lacquer) on top of a base material.
+///
+/// Light is reflected at the top of the coating according to the Fresnel equations, the rest
+/// is refracted into it and scatters at the base. Light that scatters back up leaves the
+/// coating or is reflected back down to the base, which is followed with a random walk. Nothing
+/// is lost apart from the absorption of the coating, so the material is energy conserving if
+/// the base is.
+///
+/// Only the outside of the base material is coated, hits from the inside go to the base
+/// directly.
+///
+/// The random walk has no closed form, so the material has no `eval` and lights are only found
+/// by scattered rays, which keeps the walk unbiased. Lights from a single point or direction
+/// (e.g. `PointLight` or `DirectionalLight`) do not reach the coated surface.
+pub struct LayeredMaterial {
+    base: Rc<dyn Material>,
+    /// Refraction index of the coating
+    ior: f64,
+    /// Fraction of the light that passes through the coating once at normal incidence
+    transmittance: Color,
+}
+
+impl LayeredMaterial {
+    pub fn new(base: Rc<dyn Material>, ior: f64) -> Self {
+        assert!(ior >= 1.0, "refraction index cannot be lower than 1");
+        Self {
+            base,
+            ior,
+            transmittance: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Tints the coating, `transmittance` is the color of white light that passed through it once
+    /// at normal incidence. Light that passes at an angle travels further and is tinted more.
+    pub fn transmittance(mut self, transmittance: Color) -> Self {
+        assert!(
+            transmittance.red() <= 1.0
+                && transmittance.green() <= 1.0
+                && transmittance.blue() <= 1.0,
+            "transmittance cannot be larger than 1"
+        );
+        self.transmittance = transmittance;
+        self
+    }
+
+    /// Scatters the ray at the coated base. The attenuation is in RGB or, with `wavelengths`, at
+    /// the wavelengths of the path.
+    fn scatter_with_wavelengths(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        mut wavelengths: Option<&mut SampledWavelengths>,
+    ) -> Option<(Color, Ray)> {
+        let scatter_base =
+            |r: &Ray, sampler: &mut dyn Sampler, wavelengths: Option<&mut SampledWavelengths>| {
+                match wavelengths {
+                    Some(wavelengths) => {
+                        self.base
+                            .scatter_spectral(r, hitrecord, sampler, wavelengths)
+                    }
+                    None => self.base.scatter(r, hitrecord, sampler),
+                }
+            };
+
+        if !hitrecord.hit_frontface() {
+            return scatter_base(r_in, sampler, wavelengths);
+        }
+
+        let (p, normal) = (hitrecord.get_inpact_point(), hitrecord.normal());
+        let transmittance = match wavelengths.as_deref() {
+            Some(wavelengths) => wavelengths.upsample(self.transmittance),
+            None => self.transmittance,
+        };
+        // transmittance of a pass through the coating with direction cosine `cos`
+        let pass = |cos: f64| {
+            let exponent = 1.0 / cos.abs().max(1.0e-3);
+            Color::new(
+                transmittance.red().powf(exponent),
+                transmittance.green().powf(exponent),
+                transmittance.blue().powf(exponent),
+            )
+        };
+
+        // reflection at the top of the coating
+        let unit_dir = r_in.direction().unit_vector();
+        let cos_theta = (-unit_dir).dot(normal);
+        if sampler.get_1d() < microfacet::fresnel_dielectric(cos_theta, self.ior) {
+            return Some((
+                Color::new(1.0, 1.0, 1.0),
+                Ray::new(p, unit_dir.reflect(&normal)),
+            ));
+        }
+
+        let mut direction = unit_dir.refract(&normal, 1.0 / self.ior);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+        for _ in 0..MAX_LAYER_BOUNCES {
+            // down through the coating to the base
+            throughput *= pass(direction.dot(normal));
+            let (attenuation, scattered) =
+                scatter_base(&Ray::new(p, direction), sampler, wavelengths.as_deref_mut())?;
+            throughput *= attenuation;
+
+            // light that goes through the base leaves at the bottom of the object
+            let up = scattered.direction().unit_vector();
+            let cos_up = up.dot(normal);
+            if cos_up <= 0.0 {
+                return Some((throughput, scattered));
+            }
+
+            // up through the coating, where it leaves or is reflected back down
+            throughput *= pass(cos_up);
+            if sampler.get_1d() >= microfacet::fresnel_dielectric(cos_up, 1.0 / self.ior) {
+                return Some((throughput, Ray::new(p, up.refract(&-normal, self.ior))));
+            }
+            direction = up.reflect(&normal);
+        }
+
+        None
+    }
+}
+
+impl Material for LayeredMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, None)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, Some(wavelengths))
+    }
+}
+
+/// Translucent material with light scattering below the surface (e.g. skin, wax, marble or
+/// milk), simulated with a random walk through the volume of the object.
+///
+/// Light enters the object through a smooth dielectric boundary. Inside, the distance to the next
+/// scattering event is sampled per path; at every event a fraction of the light is absorbed and
+/// the rest continues in a new direction, until the path leaves the object again. Each event is
+/// a bounce of the ray, so the object has to be closed (e.g. a sphere or closed mesh) and dense
+/// media with a high albedo need a large maximal ray depth.
+///
+/// The smooth boundary only passes light into discrete directions, so the material has no `eval`
+/// and lights are only found by scattered rays. Lights from a single point or direction (e.g.
+/// `PointLight` or `DirectionalLight`) do not reach the inside.
+pub struct Subsurface {
+    /// Fraction of the light that is scattered (instead of absorbed) at each event
+    albedo: Color,
+    /// Mean distance between scattering events per channel, in scene units
+    mean_free_path: Color,
+    /// Refraction index of the boundary
+    ior: f64,
+    /// Asymmetry of the Henyey-Greenstein phase function, `0` scatters uniformly
+    anisotropy: f64,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Color, ior: f64) -> Self {
+        assert!(
+            mean_free_path.red() > 0.0
+                && mean_free_path.green() > 0.0
+                && mean_free_path.blue() > 0.0,
+            "mean free path should be larger than 0"
+        );
+        assert!(ior >= 1.0, "refraction index cannot be lower than 1");
+        Self {
+            albedo,
+            mean_free_path,
+            ior,
+            anisotropy: 0.0,
+        }
+    }
+
+    /// Sets the asymmetry of the scattering in `(-1, 1)`, positive values scatter forward and
+    /// negative values backward.
+    pub fn anisotropy(mut self, g: f64) -> Self {
+        assert!(g.abs() < 1.0, "anisotropy should be between -1 and 1");
+        self.anisotropy = g;
+        self
+    }
+
+    /// Scatters the ray at the boundary or inside of the object. The attenuation is in RGB or,
+    /// with `wavelengths`, at the wavelengths of the path.
+    fn scatter_with_wavelengths(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Option<(Color, Ray)> {
+        let unit_dir = r_in.direction().unit_vector();
+        let (p, normal) = (hitrecord.get_inpact_point(), hitrecord.normal());
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        if hitrecord.hit_frontface() {
+            let cos_theta = (-unit_dir).dot(normal);
+            let direction =
+                match sampler.get_1d() < microfacet::fresnel_dielectric(cos_theta, self.ior) {
+                    true => unit_dir.reflect(&normal),
+                    false => unit_dir.refract(&normal, 1.0 / self.ior),
+                };
+            return Some((white, Ray::new(p, direction)));
+        }
+
+        // the ray travelled inside of the object up to the boundary
+        let (albedo, mean_free_path) = match wavelengths {
+            Some(wavelengths) => (
+                wavelengths.upsample(self.albedo),
+                wavelengths.upsample(self.mean_free_path),
+            ),
+            None => (self.albedo, self.mean_free_path),
+        };
+        let sigma = [
+            1.0 / mean_free_path.red().max(1.0e-9),
+            1.0 / mean_free_path.green().max(1.0e-9),
+            1.0 / mean_free_path.blue().max(1.0e-9),
+        ];
+        let transmittance = |distance: f64| sigma.map(|sigma| (-sigma * distance).exp());
+        let mean = |values: [f64; 3]| (values[0] + values[1] + values[2]) / 3.0;
+
+        // sample the distance with the coefficient of a random channel, so the density is the
+        // mean of the channel densities
+        let channel = ((sampler.get_1d() * 3.0) as usize).min(2);
+        let distance = -(1.0 - sampler.get_1d()).ln() / sigma[channel];
+        let boundary = hitrecord.get_t() * r_in.direction().length();
+
+        if distance < boundary {
+            let t = transmittance(distance);
+            let pdf = mean([sigma[0] * t[0], sigma[1] * t[1], sigma[2] * t[2]]);
+            let attenuation = Color::new(
+                albedo.red() * sigma[0] * t[0],
+                albedo.green() * sigma[1] * t[1],
+                albedo.blue() * sigma[2] * t[2],
+            ) * (1.0 / pdf);
+
+            let local = sampling::sample_henyey_greenstein(self.anisotropy, sampler.get_2d());
+            let direction = Onb::from_w(unit_dir).local_to_world(local);
+            return Some((
+                attenuation,
+                Ray::new(r_in.origin() + distance * unit_dir, direction),
+            ));
+        }
+
+        let t = transmittance(boundary);
+        let attenuation = Color::new(t[0], t[1], t[2]) * (1.0 / mean(t));
+
+        // leave the object or get reflected back inside
+        let cos_theta = (-unit_dir).dot(normal);
+        let direction =
+            match sampler.get_1d() < microfacet::fresnel_dielectric(cos_theta, 1.0 / self.ior) {
+                true => unit_dir.reflect(&normal),
+                false => unit_dir.refract(&normal, self.ior),
+            };
+
+        Some((attenuation, Ray::new(p, direction)))
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, None)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        self.scatter_with_wavelengths(r_in, hitrecord, sampler, Some(wavelengths))
+    }
+}
+
+/// Surface that glows with the radiance of a texture, equally in all directions, e.g. a lamp, a
+/// screen or a sign. It does not reflect any light.
+///
+/// To be sampled explicitly, the surface has to be added to the lights as `AreaLight` as well,
+/// with the `Mesh` marked by `Mesh::light`.
+pub struct DiffuseLight {
+    emission: Rc<dyn Texture>,
+    /// Scale of the emission
+    intensity: f64,
+    two_sided: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(emission: Rc<dyn Texture>) -> Self {
+        Self {
+            emission,
+            intensity: 1.0,
+            two_sided: false,
+        }
+    }
+
+    /// Scales the emitted radiance (default: `1`).
+    pub fn intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Emits light from the back of the surface as well, instead of only towards the side of the
+    /// outward normal (default: `false`).
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn is_two_sided(&self) -> bool {
+        self.two_sided
+    }
+
+    /// Returns the radiance emitted at the point `p` with surface coordinates `uv`, towards the
+    /// front (the side of the outward normal) or the back of the surface.
+    pub fn radiance(&self, uv: (f64, f64), p: Point3, front_face: bool) -> Color {
+        match front_face || self.two_sided {
+            true => self.emission.value(uv, p) * self.intensity,
+            false => Color::default(),
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _hitrecord: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, hitrecord: &HitRecord) -> Color {
+        self.radiance(
+            hitrecord.uv(),
+            hitrecord.get_inpact_point(),
+            hitrecord.hit_frontface(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sampler that returns the same value for every dimension.
+    struct ConstantSampler(f64);
+
+    impl Sampler for ConstantSampler {
+        fn samples_per_pixel(&self) -> usize {
+            1
+        }
+
+        fn start_pixel_sample(&mut self, _pixel: (usize, usize), _sample_index: usize) {}
+
+        fn get_1d(&mut self) -> f64 {
+            self.0
+        }
+
+        fn get_2d(&mut self) -> (f64, f64) {
+            (self.0, self.0)
+        }
+    }
+
+    /// Returns the hit of a ray along `direction` from `origin` with the plane `z = 0`, whose
+    /// outward normal points to `+z`.
+    fn plane_hit(material: Rc<dyn Material>, origin: Point3, direction: Vec3) -> (Ray, HitRecord) {
+        let r = Ray::new(origin, direction);
+        let t = -origin.z() / direction.z();
+        let hitrecord = HitRecord::new(
+            r.at(t),
+            &r,
+            Vec3::new(0.0, 0.0, 1.0),
+            material,
+            t,
+            (0.0, 0.0),
+        );
+        (r, hitrecord)
+    }
+
+    #[test]
+    fn dielectric_absorbs_inside_and_tints_when_entering() {
+        let glass: Rc<dyn Material> = Rc::new(
+            Dielectric::new(1.5)
+                .transmittance_at_distance(Color::new(0.5, 0.0, 1.0), 1.0)
+                .tint(Color::new(0.9, 0.8, 0.7)),
+        );
+        // the reflectance at normal incidence is 4%, so the ray is refracted
+        let mut sampler = ConstantSampler(0.5);
+
+        // leaving after traveling a distance of 2 inside, a channel without transmittance is
+        // absorbed completely
+        let (r, hitrecord) = plane_hit(
+            glass.clone(),
+            Point3::new(0.0, 0.0, -2.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let (attenuation, _) = glass.scatter(&r, &hitrecord, &mut sampler).unwrap();
+        assert!((attenuation.red() - 0.25).abs() < 1e-12);
+        assert_eq!(attenuation.green(), 0.0);
+        assert!((attenuation.blue() - 1.0).abs() < 1e-12);
+
+        let (r, hitrecord) = plane_hit(
+            glass.clone(),
+            Point3::new(0.0, 0.0, 3.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        let (attenuation, _) = glass.scatter(&r, &hitrecord, &mut sampler).unwrap();
+        assert!((attenuation.red() - 0.9).abs() < 1e-12);
+        assert!((attenuation.blue() - 0.7).abs() < 1e-12);
+    }
+
+    /// Returns the lobes of a rough, partly transmissive surface with every lobe enabled.
+    fn principled_lobes(wo: Vec3) -> Option<PrincipledLobes> {
+        PrincipledLobes::new(
+            Color::new(0.8, 0.4, 0.2),
+            0.3,
+            0.5,
+            TrowbridgeReitz::new(0.25, 0.25),
+            0.5,
+            0.2,
+            0.5,
+            1.0,
+            0.7,
+            0.4,
+            1.5,
+            wo,
+        )
+    }
+
+    #[test]
+    fn principled_bsdf_at_grazing_angles() {
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let lobes = principled_lobes(wo).unwrap();
+
+        // light arriving along the surface does not scatter (instead of dividing by zero)
+        for wi in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(-0.6, 0.8, 0.0)] {
+            let f = lobes.f(wo, wi);
+            assert_eq!((f.red(), f.green(), f.blue()), (0.0, 0.0, 0.0));
+        }
+
+        let f = lobes.f(wo, Vec3::new(-1.0, 0.0, 1.0e-12).unit_vector());
+        assert!(f.red().is_finite() && f.green().is_finite() && f.blue().is_finite());
+    }
+
+    #[test]
+    fn principled_bsdf_samples_are_finite() {
+        for wo in [
+            Vec3::new(0.6, 0.0, 0.8),
+            Vec3::new(1.0, 0.0, 1.0e-9).unit_vector(),
+        ] {
+            let Some(lobes) = principled_lobes(wo) else {
+                continue;
+            };
+
+            let n = 16;
+            for i in 0..n * n * 4 {
+                let lobe_u = (i % 4) as f64 / 4.0 + 0.125;
+                let u = (
+                    ((i / 4 % n) as f64 + 0.5) / n as f64,
+                    ((i / 4 / n) as f64 + 0.5) / n as f64,
+                );
+                let Some(wi) = lobes.sample_wi(wo, lobe_u, u) else {
+                    continue;
+                };
+
+                let pdf = lobes.pdf(wo, wi);
+                let f = lobes.f(wo, wi);
+                assert!(pdf.is_finite() && pdf >= 0.0);
+                assert!(f.red().is_finite() && f.green().is_finite() && f.blue().is_finite());
+            }
+        }
+    }
+}
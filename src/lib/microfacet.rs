@@ -0,0 +1,184 @@
+use super::{sampling, Vec3};
+use std::f64::consts::PI;
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals with the Smith masking-shadowing
+/// function (Walter et al., "Microfacet Models for Refraction through Rough Surfaces").
+///
+/// All directions are in the local shading space, where the surface normal is the `z` axis (see
+/// `Onb`), and point away from the surface.
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    /// Returns the distribution of a surface with a perceptual `roughness` in `[0, 1]`, which is
+    /// squared to get the `alpha` of the distribution.
+    pub fn from_roughness(roughness: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&roughness),
+            "roughness should be between 0 and 1"
+        );
+        let alpha = roughness * roughness;
+        Self::new(alpha, alpha)
+    }
+
+    /// Returns a (possibly anisotropic) distribution with the `alpha` along the `x` and `y` axes.
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self { alpha_x, alpha_y }
+    }
+
+    /// Returns `true` if the surface is so smooth that it should be handled as a perfect mirror,
+    /// the distribution is numerically unstable for these values.
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1.0e-3
+    }
+
+    /// Returns the density of microfacets with normal `wm`.
+    pub fn d(&self, wm: Vec3) -> f64 {
+        if wm.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let e = (wm.x() / self.alpha_x).powi(2) + (wm.y() / self.alpha_y).powi(2) + wm.z().powi(2);
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's auxiliary function, measuring the invisible microfacet area per visible area.
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let alpha2_tan2 =
+            ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / w.z().powi(2);
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Returns the fraction of microfacets that is visible from direction `w`.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Returns the fraction of microfacets that is visible from both `wo` and `wi` (height
+    /// correlated masking-shadowing).
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Returns the density of the microfacet normal `wm` among the normals visible from `w`.
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    /// Samples a microfacet normal that is visible from `w`, distributed according to
+    /// `d_visible` (Heitz, "Sampling the GGX Distribution of Visible Normals").
+    pub fn sample_wm(&self, w: Vec3, u: (f64, f64)) -> Vec3 {
+        // transform `w` to the hemispherical configuration
+        let mut wh = Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).unit_vector();
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+
+        // orthonormal basis around `wh`
+        let t1 = if wh.z() < 0.99999 {
+            Vec3::new(0.0, 0.0, 1.0).cross(wh).unit_vector()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        // uniform point on the disk, warped to the projection of the visible hemisphere
+        let p = sampling::sample_uniform_disk_concentric(u);
+        let h = (1.0 - p.x() * p.x()).sqrt();
+        let s = (1.0 + wh.z()) / 2.0;
+        let (px, py) = (p.x(), (1.0 - s) * h + s * p.y());
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        // back to the ellipsoid configuration
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1.0e-6),
+        )
+        .unit_vector()
+    }
+}
+
+/// Returns `w` mirrored around `n`.
+pub fn reflect(w: Vec3, n: Vec3) -> Vec3 {
+    -w + 2.0 * w.dot(n) * n
+}
+
+/// Returns `w` refracted through a surface with normal `n` (on the same side as `w`), or `None`
+/// for total internal reflection.
+///
+/// Arguments:
+///
+/// * `eta`: Refraction index of the side `w` goes to divided by the one of the side of `w`.
+pub fn refract(w: Vec3, n: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = w.dot(n);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * n)
+}
+
+/// Returns the fraction of light reflected at a smooth dielectric interface (unpolarized Fresnel
+/// equations).
+///
+/// Arguments:
+///
+/// * `cos_i`: Cosine of the angle between the incoming direction and the normal.
+/// * `eta`: Refraction index of the other side divided by the one of the incoming side.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0);
+    let (cos_i, eta) = match cos_i < 0.0 {
+        true => (-cos_i, 1.0 / eta),
+        false => (cos_i, eta),
+    };
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Returns the fraction of light reflected by a conductor with complex refraction index
+/// `eta + i k`, for a single wavelength.
+///
+/// Arguments:
+///
+/// * `cos_i`: Cosine of the angle between the incoming direction and the normal.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_p + r_s) / 2.0
+}
@@ -1,15 +1,34 @@
-mod camera;
+pub mod adaptive;
+pub mod cameras;
 mod color;
+pub mod display;
+pub mod environment;
+pub mod film;
+pub mod filters;
 mod hitrecord;
 pub mod hittables;
+pub mod ies;
+pub mod image;
+pub mod light_samplers;
+pub mod lights;
 pub mod materials;
+pub mod microfacet;
+pub mod normal_maps;
+mod onb;
+pub mod options;
 mod ray;
+pub mod samplers;
+pub mod sampling;
+pub mod scheduler;
+pub mod spectrum;
+pub mod textures;
+pub mod thin_film;
 pub mod utils;
 mod vec3;
 
-pub use camera::Camera;
 pub use color::Color;
 pub use hitrecord::HitRecord;
+pub use onb::Onb;
 pub use ray::Ray;
 pub use vec3::{Point3, Vec3};
 
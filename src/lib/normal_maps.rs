@@ -0,0 +1,192 @@
+use super::{
+    spectrum::SampledWavelengths,
+    traits::{FloatTexture, Material, Sampler, Texture},
+    Color, HitRecord, Ray, Vec3,
+};
+use std::rc::Rc;
+
+/// Step in surface coordinates used for the finite differences of bump maps.
+const BUMP_DELTA: f64 = 1.0e-4;
+
+/// Perturbs the shading normal of a material with a tangent space normal map, where red, green
+/// and blue encode the normal along `dpdu`, `dpdv` and the surface normal (OpenGL convention).
+/// The map should hold linear values, e.g. loaded with `ImageTexture::open_linear`.
+pub struct NormalMap {
+    base: Rc<dyn Material>,
+    map: Rc<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(base: Rc<dyn Material>, map: Rc<dyn Texture>) -> Self {
+        Self { base, map }
+    }
+
+    /// Returns the outward shading normal at `hitrecord`.
+    fn normal(&self, hitrecord: &HitRecord) -> Vec3 {
+        let color = self.map.value(hitrecord.uv(), hitrecord.get_inpact_point());
+        let local =
+            2.0 * Vec3::new(color.red(), color.green(), color.blue()) - Vec3::new(1.0, 1.0, 1.0);
+
+        let n = hitrecord.outward_normal();
+        let tangent = (hitrecord.dpdu() - n.dot(hitrecord.dpdu()) * n).unit_vector();
+        let mut bitangent = n.cross(tangent);
+        if bitangent.dot(hitrecord.dpdv()) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        local.x() * tangent + local.y() * bitangent + local.z().max(1.0e-3) * n
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.scatter(r_in, &shaded, sampler)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base
+            .scatter_spectral(r_in, &shaded, sampler, wavelengths)
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.eval(r_in, &shaded, wi)
+    }
+
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.eval_spectral(r_in, &shaded, wi, wavelengths)
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.pdf(r_in, &shaded, wi)
+    }
+
+    fn emitted(&self, r_in: &Ray, hitrecord: &HitRecord) -> Color {
+        self.base.emitted(r_in, hitrecord)
+    }
+}
+
+/// Perturbs the shading normal of a material as if the surface was displaced along its normal
+/// by a height map.
+pub struct BumpMap {
+    base: Rc<dyn Material>,
+    height: Rc<dyn FloatTexture>,
+    /// Displacement of a height of 1
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(base: Rc<dyn Material>, height: Rc<dyn FloatTexture>, scale: f64) -> Self {
+        Self {
+            base,
+            height,
+            scale,
+        }
+    }
+
+    /// Returns the outward shading normal at `hitrecord`.
+    fn normal(&self, hitrecord: &HitRecord) -> Vec3 {
+        let (u, v) = hitrecord.uv();
+        let p = hitrecord.get_inpact_point();
+        let (dpdu, dpdv) = (hitrecord.dpdu(), hitrecord.dpdv());
+        let n = hitrecord.outward_normal();
+
+        let height = self.height.value((u, v), p);
+        let slope_u = (self
+            .height
+            .value((u + BUMP_DELTA, v), p + BUMP_DELTA * dpdu)
+            - height)
+            / BUMP_DELTA;
+        let slope_v = (self
+            .height
+            .value((u, v + BUMP_DELTA), p + BUMP_DELTA * dpdv)
+            - height)
+            / BUMP_DELTA;
+
+        let normal = (dpdu + self.scale * slope_u * n).cross(dpdv + self.scale * slope_v * n);
+        match normal.dot(n) < 0.0 {
+            true => -normal,
+            false => normal,
+        }
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.scatter(r_in, &shaded, sampler)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base
+            .scatter_spectral(r_in, &shaded, sampler, wavelengths)
+    }
+
+    fn eval(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Color {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.eval(r_in, &shaded, wi)
+    }
+
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.eval_spectral(r_in, &shaded, wi, wavelengths)
+    }
+
+    fn pdf(&self, r_in: &Ray, hitrecord: &HitRecord, wi: Vec3) -> Option<f64> {
+        let shaded = shade(r_in, hitrecord, self.normal(hitrecord));
+        self.base.pdf(r_in, &shaded, wi)
+    }
+
+    fn emitted(&self, r_in: &Ray, hitrecord: &HitRecord) -> Color {
+        self.base.emitted(r_in, hitrecord)
+    }
+}
+
+/// Returns `hitrecord` with the shading normal `outward_normal`. The geometric normal is kept
+/// when the incoming ray would come from below the perturbed surface.
+fn shade(r_in: &Ray, hitrecord: &HitRecord, outward_normal: Vec3) -> HitRecord {
+    let shaded = hitrecord.with_shading_normal(outward_normal);
+    match r_in.direction().dot(shaded.normal()) < 0.0 {
+        true => shaded,
+        false => hitrecord.clone(),
+    }
+}
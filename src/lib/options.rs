@@ -0,0 +1,269 @@
+use super::{
+    cameras::{CameraKind, Convergence, StereoLayout, ViewSize},
+    display::{DisplayPipeline, ToneMapper, TransferFunction},
+    environment::SunPosition,
+    filters::FilterKind,
+    light_samplers::LightSamplerKind,
+    samplers::SamplerKind,
+    scheduler::{Region, TileOrder},
+};
+use std::{path::PathBuf, str::FromStr};
+
+/// Render settings that can be changed from the command line.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Sampler used to generate the pixel, lens and bounce samples (`--sampler`).
+    pub sampler: SamplerKind,
+    /// Number of samples taken for every pixel (`--spp`). With adaptive sampling this is the
+    /// maximum number of samples.
+    pub samples_per_pixel: usize,
+    /// Enables adaptive sampling. A pixel stops taking samples once the 95% confidence interval
+    /// of its luminance is smaller than this fraction of its mean luminance (`--adaptive`).
+    pub adaptive_threshold: Option<f64>,
+    /// Minimal number of samples taken for every pixel with adaptive sampling (`--min-spp`).
+    pub min_samples_per_pixel: usize,
+    /// Path to write a heatmap of the number of samples used per pixel to (`--heatmap`).
+    pub heatmap: Option<PathBuf>,
+    /// Renders the image progressively, adding this many samples per pixel in every pass over the
+    /// image (`--pass-spp`). Without it the image is rendered in a single pass.
+    pub pass_samples_per_pixel: Option<usize>,
+    /// Path to write an intermediate image to after every `save_every` passes (`--preview`).
+    pub preview: Option<PathBuf>,
+    /// Path to save the accumulated samples to after every `save_every` passes and at the end of
+    /// the render (`--checkpoint`).
+    pub checkpoint: Option<PathBuf>,
+    /// Number of passes between saving the preview and checkpoint (`--save-every`).
+    pub save_every: usize,
+    /// Checkpoint to continue rendering from (`--resume`). The render should use the same scene,
+    /// resolution, sampler, seed and samples per pass as the render that saved the checkpoint.
+    pub resume: Option<PathBuf>,
+    /// Seed of the sampler, different seeds result in different noise patterns (`--seed`).
+    pub seed: u64,
+    /// Renders only this part of the image (`--region`).
+    pub region: Option<Region>,
+    /// Width and height of the tiles the image is split into (`--tile-size`).
+    pub tile_size: usize,
+    /// Order in which the tiles are rendered (`--tile-order`).
+    pub tile_order: TileOrder,
+    /// Reconstruction filter used to weigh the samples around a pixel (`--filter`).
+    pub filter: FilterKind,
+    /// Radius of the reconstruction filter in pixels, every filter has its own default radius
+    /// (`--filter-radius`).
+    pub filter_radius: Option<f64>,
+    /// Renders with sampled wavelengths instead of RGB, which shows dispersion (`--spectral`).
+    pub spectral: bool,
+    /// Equirectangular image (Radiance HDR or PPM) that surrounds and lights the scene instead of
+    /// the sky gradient (`--environment`).
+    pub environment: Option<PathBuf>,
+    /// Rotation of the environment around the up axis in degrees (`--environment-rotation`).
+    pub environment_rotation: f64,
+    /// Scale of the radiance of the environment (`--environment-intensity`).
+    pub environment_intensity: f64,
+    /// Renders with an orthographic projection of this view size instead of the perspective one
+    /// (`--orthographic`).
+    pub orthographic: Option<ViewSize>,
+    /// Projection of the camera (`--camera`).
+    pub camera: CameraKind,
+    /// Field of view of the fisheye cameras in degrees (`--fisheye-fov`).
+    pub fisheye_fov: f64,
+    /// Renders both eyes of a stereo camera in this layout (`--stereo`).
+    pub stereo: Option<StereoLayout>,
+    /// Distance between the eyes of the stereo camera (`--interocular`).
+    pub interocular: f64,
+    /// Distance at which the eyes of the stereo camera converge, instead of the focus distance
+    /// (`--convergence`).
+    pub convergence_distance: Option<f64>,
+    /// Way the eyes of the stereo camera converge (`--convergence-mode`).
+    pub convergence: Convergence,
+    /// Strategy for choosing the light to sample at every bounce (`--light-sampler`).
+    pub light_sampler: LightSamplerKind,
+    /// Lights the scene with a physical sky and sun at this position instead of the sky
+    /// gradient (`--sun`).
+    pub sun: Option<SunPosition>,
+    /// Haziness of the physical sky (`--turbidity`).
+    pub turbidity: f64,
+    /// Gray level of the ground below the physical sky (`--ground-albedo`).
+    pub ground_albedo: f64,
+    /// Converts the rendered radiance to the colors of the image (`--exposure`, `--tonemap`,
+    /// `--transfer` and `--no-dither`).
+    pub display: DisplayPipeline,
+}
+
+impl RenderOptions {
+    /// Parses the options from the command line arguments (without the program name). Options
+    /// that are not given keep their default value.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--sampler" => options.sampler = parse_value(&mut args, &arg)?,
+                "--spp" => options.samples_per_pixel = parse_value(&mut args, &arg)?,
+                "--seed" => options.seed = parse_value(&mut args, &arg)?,
+                "--adaptive" => options.adaptive_threshold = Some(parse_value(&mut args, &arg)?),
+                "--min-spp" => options.min_samples_per_pixel = parse_value(&mut args, &arg)?,
+                "--heatmap" => options.heatmap = Some(parse_value(&mut args, &arg)?),
+                "--pass-spp" => {
+                    options.pass_samples_per_pixel = Some(parse_value(&mut args, &arg)?)
+                }
+                "--preview" => options.preview = Some(parse_value(&mut args, &arg)?),
+                "--checkpoint" => options.checkpoint = Some(parse_value(&mut args, &arg)?),
+                "--save-every" => options.save_every = parse_value(&mut args, &arg)?,
+                "--resume" => options.resume = Some(parse_value(&mut args, &arg)?),
+                "--region" => options.region = Some(parse_value(&mut args, &arg)?),
+                "--tile-size" => options.tile_size = parse_value(&mut args, &arg)?,
+                "--tile-order" => options.tile_order = parse_value(&mut args, &arg)?,
+                "--filter" => options.filter = parse_value(&mut args, &arg)?,
+                "--filter-radius" => options.filter_radius = Some(parse_value(&mut args, &arg)?),
+                "--exposure" => options.display.exposure = parse_value(&mut args, &arg)?,
+                "--tonemap" => {
+                    options.display.tone_mapper = parse_value::<ToneMapper, _>(&mut args, &arg)?
+                }
+                "--transfer" => {
+                    options.display.transfer = parse_value::<TransferFunction, _>(&mut args, &arg)?
+                }
+                "--no-dither" => options.display.dither = false,
+                "--spectral" => options.spectral = true,
+                "--environment" => options.environment = Some(parse_value(&mut args, &arg)?),
+                "--environment-rotation" => {
+                    options.environment_rotation = parse_value(&mut args, &arg)?
+                }
+                "--environment-intensity" => {
+                    options.environment_intensity = parse_value(&mut args, &arg)?
+                }
+                "--orthographic" => options.orthographic = Some(parse_value(&mut args, &arg)?),
+                "--camera" => options.camera = parse_value(&mut args, &arg)?,
+                "--fisheye-fov" => options.fisheye_fov = parse_value(&mut args, &arg)?,
+                "--stereo" => options.stereo = Some(parse_value(&mut args, &arg)?),
+                "--interocular" => options.interocular = parse_value(&mut args, &arg)?,
+                "--convergence" => {
+                    options.convergence_distance = Some(parse_value(&mut args, &arg)?)
+                }
+                "--convergence-mode" => options.convergence = parse_value(&mut args, &arg)?,
+                "--light-sampler" => options.light_sampler = parse_value(&mut args, &arg)?,
+                "--sun" => options.sun = Some(parse_value(&mut args, &arg)?),
+                "--turbidity" => options.turbidity = parse_value(&mut args, &arg)?,
+                "--ground-albedo" => options.ground_albedo = parse_value(&mut args, &arg)?,
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+
+        if options.samples_per_pixel == 0 {
+            return Err("`--spp` should be at least 1".to_string());
+        }
+        if options.pass_samples_per_pixel == Some(0) {
+            return Err("`--pass-spp` should be at least 1".to_string());
+        }
+        if options.tile_size == 0 {
+            return Err("`--tile-size` should be at least 1".to_string());
+        }
+        if matches!(options.filter_radius, Some(radius) if radius <= 0.0) {
+            return Err("`--filter-radius` should be larger than 0".to_string());
+        }
+        if options.save_every == 0 {
+            return Err("`--save-every` should be at least 1".to_string());
+        }
+        if matches!(options.adaptive_threshold, Some(threshold) if threshold <= 0.0) {
+            return Err("`--adaptive` should be larger than 0".to_string());
+        }
+        if options.environment_intensity < 0.0 {
+            return Err("`--environment-intensity` cannot be negative".to_string());
+        }
+        if options.environment.is_some() && options.sun.is_some() {
+            return Err("`--environment` and `--sun` cannot be combined".to_string());
+        }
+        if options.orthographic.is_some() && options.camera != CameraKind::Perspective {
+            return Err("`--orthographic` needs the perspective camera".to_string());
+        }
+        if options.fisheye_fov <= 0.0 || options.fisheye_fov > 360.0 {
+            return Err("`--fisheye-fov` should be larger than 0 and at most 360".to_string());
+        }
+        if options.stereo.is_some()
+            && (options.orthographic.is_some()
+                || !matches!(
+                    options.camera,
+                    CameraKind::Perspective | CameraKind::Equirectangular
+                ))
+        {
+            return Err(
+                "`--stereo` needs the perspective or equirectangular camera without `--orthographic`"
+                    .to_string(),
+            );
+        }
+        if options.interocular < 0.0 {
+            return Err("`--interocular` cannot be negative".to_string());
+        }
+        if matches!(options.convergence_distance, Some(distance) if distance <= 0.0) {
+            return Err("`--convergence` should be larger than 0".to_string());
+        }
+        if !(1.7..=10.0).contains(&options.turbidity) {
+            return Err("`--turbidity` should be between 1.7 and 10".to_string());
+        }
+        if !(0.0..=1.0).contains(&options.ground_albedo) {
+            return Err("`--ground-albedo` should be between 0 and 1".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            sampler: SamplerKind::Sobol,
+            samples_per_pixel: 200,
+            seed: 0,
+            adaptive_threshold: None,
+            min_samples_per_pixel: 16,
+            heatmap: None,
+            pass_samples_per_pixel: None,
+            preview: None,
+            checkpoint: None,
+            save_every: 1,
+            resume: None,
+            region: None,
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
+            filter: FilterKind::Box,
+            filter_radius: None,
+            spectral: false,
+            environment: None,
+            environment_rotation: 0.0,
+            environment_intensity: 1.0,
+            orthographic: None,
+            camera: CameraKind::Perspective,
+            fisheye_fov: 180.0,
+            stereo: None,
+            interocular: 0.065,
+            convergence_distance: None,
+            convergence: Convergence::OffAxis,
+            light_sampler: LightSamplerKind::Power,
+            sun: None,
+            turbidity: 3.0,
+            ground_albedo: 0.3,
+            display: DisplayPipeline::default(),
+        }
+    }
+}
+
+/// Parses the value following `flag`.
+fn parse_value<T, I>(args: &mut I, flag: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: ToString,
+    I: Iterator<Item = String>,
+{
+    let value = args
+        .next()
+        .ok_or_else(|| format!("missing value for `{}`", flag))?;
+
+    value.parse().map_err(|e: T::Err| {
+        format!(
+            "invalid value `{}` for `{}`: {}",
+            value,
+            flag,
+            e.to_string()
+        )
+    })
+}
@@ -0,0 +1,512 @@
+use super::{
+    traits::Sampler,
+    utils::{self, Pcg32, ONE_MINUS_EPSILON},
+};
+use std::str::FromStr;
+
+/// Sampler that returns independent uniform random values (white noise).
+pub struct IndependentSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            rng: Pcg32::new(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.rng = Pcg32::new(utils::hash(&[
+            pixel.0 as u64,
+            pixel.1 as u64,
+            sample_index as u64,
+            self.seed,
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.next_double()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.next_double(), self.rng.next_double())
+    }
+}
+
+/// Sampler that divides every dimension into strata and places one jittered sample in each of
+/// them. The strata of different dimensions are shuffled independently, so dimensions do not
+/// correlate with each other.
+///
+/// 2D dimensions are divided into a `x_strata * y_strata` grid that is as square as possible.
+/// Once all strata are used (sample indices past the grid size) a new shuffle is started.
+pub struct StratifiedSampler {
+    samples_per_pixel: usize,
+    x_strata: usize,
+    y_strata: usize,
+    seed: u64,
+    pixel: (usize, usize),
+    sample_index: usize,
+    dimension: usize,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x_strata = (samples_per_pixel as f64).sqrt() as usize;
+        let y_strata = samples_per_pixel / x_strata;
+
+        Self {
+            samples_per_pixel,
+            x_strata,
+            y_strata,
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed),
+        }
+    }
+
+    /// Returns the stratum the current sample uses in the current dimension.
+    fn stratum(&self, strata: usize) -> usize {
+        let hash = utils::hash(&[
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            (self.sample_index / strata) as u64,
+            self.seed,
+        ]);
+
+        permutation_element(
+            (self.sample_index % strata) as u32,
+            strata as u32,
+            hash as u32,
+        ) as usize
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.rng = Pcg32::new(utils::hash(&[
+            pixel.0 as u64,
+            pixel.1 as u64,
+            sample_index as u64,
+            self.seed,
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum(self.samples_per_pixel);
+        self.dimension += 1;
+
+        ((stratum as f64 + self.rng.next_double()) / self.samples_per_pixel as f64)
+            .min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum(self.x_strata * self.y_strata);
+        self.dimension += 2;
+
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        (
+            ((x as f64 + self.rng.next_double()) / self.x_strata as f64).min(ONE_MINUS_EPSILON),
+            ((y as f64 + self.rng.next_double()) / self.y_strata as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+/// Prime bases of the Halton dimensions.
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Sampler based on the Halton sequence (<https://en.wikipedia.org/wiki/Halton_sequence>), which
+/// uses the radical inverse in a different prime base for every dimension. Every pixel uses its
+/// own Owen scrambling of the sequence to avoid structured artifacts between pixels.
+///
+/// Dimensions past the number of available prime bases fall back to independent random values.
+pub struct HaltonSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    pixel_hash: u64,
+    sample_index: usize,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            pixel_hash: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_hash = utils::hash(&[pixel.0 as u64, pixel.1 as u64, self.seed]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let hash = utils::hash(&[self.pixel_hash, dimension as u64]);
+        if dimension >= PRIMES.len() {
+            return utils::hash_to_double(utils::hash(&[hash, self.sample_index as u64]));
+        }
+
+        owen_scrambled_radical_inverse(PRIMES[dimension], self.sample_index as u64, hash)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Sampler based on the Sobol sequence (<https://en.wikipedia.org/wiki/Sobol_sequence>). Only the
+/// first two Sobol dimensions are used, every requested dimension is _padded_ from them by
+/// shuffling the sample index and applying its own Owen scrambling. This keeps every 1D and 2D
+/// projection well distributed for any number of dimensions.
+///
+/// The distribution is best when the number of samples per pixel is a power of two.
+pub struct SobolSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    pixel: (usize, usize),
+    sample_index: usize,
+    dimension: usize,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+            pixel: (0, 0),
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Returns the hash used to shuffle and scramble the current dimension.
+    fn dimension_hash(&self) -> u64 {
+        utils::hash(&[
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            self.seed,
+        ])
+    }
+
+    /// Returns the sample index shuffled within its block of `samples_per_pixel` indices.
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        let block = self.sample_index / self.samples_per_pixel * self.samples_per_pixel;
+        let index = permutation_element(
+            (self.sample_index % self.samples_per_pixel) as u32,
+            self.samples_per_pixel as u32,
+            hash as u32,
+        );
+
+        block as u32 + index
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel = pixel;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.dimension_hash();
+        self.dimension += 1;
+
+        let index = self.shuffled_index(hash);
+        sobol_to_double(fast_owen_scramble(
+            index.reverse_bits(),
+            (hash >> 32) as u32,
+        ))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.dimension_hash();
+        self.dimension += 2;
+
+        let index = self.shuffled_index(hash);
+        let seeds = utils::mix_bits(hash);
+        (
+            sobol_to_double(fast_owen_scramble(index.reverse_bits(), seeds as u32)),
+            sobol_to_double(fast_owen_scramble(
+                sobol_second_dimension(index),
+                (seeds >> 32) as u32,
+            )),
+        )
+    }
+}
+
+/// The available sampler implementations, parsed from their lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn build(&self, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(samples_per_pixel, seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            Self::Halton => Box::new(HaltonSampler::new(samples_per_pixel, seed)),
+            Self::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(Self::Independent),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            "sobol" => Ok(Self::Sobol),
+            _ => Err(format!(
+                "unknown sampler `{}` (expected `independent`, `stratified`, `halton` or `sobol`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the element at index `i` of a random permutation of `0..l` that is chosen by `p`,
+/// without generating the permutation itself (Kensler, "Correlated Multi-Jittered Sampling").
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // cycle walk until the permuted index lands inside the range
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < l {
+            break;
+        }
+    }
+
+    i.wrapping_add(p) % l
+}
+
+/// Returns the radical inverse of `a` in `base`, where every digit is permuted depending on the
+/// digits before it (Owen scrambling). The permutations are chosen by `hash`.
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let limit = u64::MAX / base - base;
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits = 0;
+
+    // keep going after `a` reaches zero, the leading zero digits have to be scrambled as well
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 && reversed_digits < limit {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = utils::mix_bits(hash ^ reversed_digits);
+        let digit = permutation_element(digit as u32, base as u32, digit_hash as u32) as u64;
+
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+
+    (inv_base_m * reversed_digits as f64).min(ONE_MINUS_EPSILON)
+}
+
+/// Returns the second dimension of the Sobol sequence as fixed point number. The first dimension
+/// is the bit reversal of the index.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    // direction numbers of the primitive polynomial `x + 1`
+    let mut v = 1_u32 << 31;
+    let mut result = 0;
+
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+
+    result
+}
+
+/// Owen scrambles a fixed point number using a hash based approximation (Laine and Karras).
+fn fast_owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+fn sobol_to_double(v: u32) -> f64 {
+    (v as f64 / (1_u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    /// Returns the first `dimensions` values of every sample of a pixel.
+    fn samples(
+        sampler: &mut dyn Sampler,
+        pixel: (usize, usize),
+        dimensions: usize,
+    ) -> Vec<Vec<f64>> {
+        (0..sampler.samples_per_pixel())
+            .map(|index| {
+                sampler.start_pixel_sample(pixel, index);
+                (0..dimensions).map(|_| sampler.get_1d()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_are_in_unit_interval_and_repeatable() {
+        for kind in KINDS {
+            let mut sampler = kind.build(16, 7);
+            let values = samples(sampler.as_mut(), (3, 5), 40);
+            assert!(
+                values.iter().flatten().all(|v| (0.0..1.0).contains(v)),
+                "{:?}",
+                kind
+            );
+            assert_eq!(values, samples(sampler.as_mut(), (3, 5), 40), "{:?}", kind);
+            assert_ne!(values, samples(sampler.as_mut(), (4, 5), 40), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn dimensions_are_stratified() {
+        let spp = 16;
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let mut sampler = kind.build(spp, 7);
+            let values = samples(sampler.as_mut(), (1, 2), 8);
+
+            // Halton only stratifies the dimensions with base 2 over 16 samples
+            let dimensions = match kind {
+                SamplerKind::Halton => 1,
+                _ => 8,
+            };
+            for dimension in 0..dimensions {
+                let mut strata = vec![false; spp];
+                for sample in &values {
+                    strata[(sample[dimension] * spp as f64) as usize] = true;
+                }
+                assert!(
+                    strata.iter().all(|&s| s),
+                    "{:?} dimension {}",
+                    kind,
+                    dimension
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_2d_samples_are_stratified() {
+        let mut sampler = SobolSampler::new(16, 3);
+        for dimension in 0..4 {
+            let mut cells = [0; 16];
+            for index in 0..16 {
+                sampler.start_pixel_sample((2, 9), index);
+                for _ in 0..dimension {
+                    sampler.get_2d();
+                }
+                let (x, y) = sampler.get_2d();
+                cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+            }
+            assert_eq!(cells, [1; 16], "dimension {}", dimension);
+        }
+    }
+
+    #[test]
+    fn parse_sampler_kind() {
+        for (name, kind) in ["independent", "stratified", "halton", "sobol"]
+            .into_iter()
+            .zip(KINDS)
+        {
+            assert_eq!(name.parse(), Ok(kind));
+        }
+        assert!("random".parse::<SamplerKind>().is_err());
+    }
+}
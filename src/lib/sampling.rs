@@ -0,0 +1,332 @@
+use super::{utils::ONE_MINUS_EPSILON, Vec3};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// Maps a sample from the unit square to a point in the unit circle (with `z` set to `0`), using
+/// the concentric mapping of Shirley and Chiu. Unlike rejection sampling this keeps the
+/// stratification of the input sample intact.
+pub fn sample_uniform_disk_concentric(u: (f64, f64)) -> Vec3 {
+    // map the sample to `[-1, 1]^2`
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return Vec3::default();
+    }
+
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Maps a sample from the unit square to a point on the unit sphere.
+pub fn sample_uniform_sphere(u: (f64, f64)) -> Vec3 {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Maps a sample from the unit square to a direction in the cone around the `z` axis with an
+/// opening angle of `acos(cos_theta_max)`.
+pub fn sample_uniform_cone(u: (f64, f64), cos_theta_max: f64) -> Vec3 {
+    let cos_theta = 1.0 - u.0 * (1.0 - cos_theta_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Maps a sample from the unit square to the barycentric coordinates of a uniformly distributed
+/// point in a triangle.
+pub fn sample_uniform_triangle(u: (f64, f64)) -> [f64; 3] {
+    let su = u.0.sqrt();
+    [1.0 - su, u.1 * su, (1.0 - u.1) * su]
+}
+
+/// Maps a sample from the unit cube to a point in the unit sphere.
+pub fn sample_uniform_ball(u: (f64, f64), w: f64) -> Vec3 {
+    w.cbrt() * sample_uniform_sphere(u)
+}
+
+/// Maps a sample from the unit square to a direction on the hemisphere around the `z` axis, with
+/// a density proportional to the cosine of the angle to the axis (Malley's method).
+pub fn sample_cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let d = sample_uniform_disk_concentric(u);
+    let z = (1.0 - d.x() * d.x() - d.y() * d.y()).max(0.0).sqrt();
+
+    Vec3::new(d.x(), d.y(), z)
+}
+
+/// Returns the density of `sample_cosine_hemisphere` for a direction with `cos_theta`.
+pub fn cosine_hemisphere_pdf(cos_theta: f64) -> f64 {
+    cos_theta.max(0.0) / PI
+}
+
+/// Maps a sample from the unit square to a direction around the `z` axis distributed according
+/// to the Henyey-Greenstein phase function with asymmetry `g` (positive values scatter forward,
+/// i.e. along `z`).
+pub fn sample_henyey_greenstein(g: f64, u: (f64, f64)) -> Vec3 {
+    let cos_theta = if g.abs() < 1.0e-3 {
+        1.0 - 2.0 * u.0
+    } else {
+        let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u.0);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Piecewise constant distribution over `[0, 1)`, with a density proportional to a function
+/// given by its values on equally sized intervals.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    /// Cumulative distribution at the start of every interval, with `1` at the end
+    cdf: Vec<f64>,
+    /// Integral of the function over `[0, 1)`
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Returns the distribution for the (non-negative) `function` values. A function that is `0`
+    /// everywhere results in a uniform distribution.
+    pub fn new(function: Vec<f64>) -> Self {
+        assert!(!function.is_empty(), "distribution should not be empty");
+        let count = function.len() as f64;
+
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for value in &function {
+            cdf.push(cdf[cdf.len() - 1] + value.max(0.0) / count);
+        }
+
+        let integral = cdf[function.len()];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / count);
+        }
+
+        Self {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps `u` to a point in `[0, 1)`. Returns the point, its density and the index of its
+    /// interval.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // last interval with a cdf that is not larger than `u`
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .clamp(1, self.function.len())
+            - 1;
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = match width > 0.0 {
+            true => (u - self.cdf[index]) / width,
+            false => 0.0,
+        };
+        let x = ((index as f64 + offset) / self.function.len() as f64).min(ONE_MINUS_EPSILON);
+
+        (x, self.pdf(x), index)
+    }
+
+    /// Returns the probability of sampling a point in the interval `index`.
+    pub fn interval_probability(&self, index: usize) -> f64 {
+        self.cdf[index + 1] - self.cdf[index]
+    }
+
+    /// Returns the density of sampling `x`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.function.len() as f64) as usize).min(self.function.len() - 1);
+        match self.integral > 0.0 {
+            true => self.function[index].max(0.0) / self.integral,
+            false => 1.0,
+        }
+    }
+}
+
+/// Piecewise constant distribution over `[0, 1)^2`, with a density proportional to a function
+/// given by its values on a grid.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    /// Distribution of `x` in every row
+    rows: Vec<Distribution1D>,
+    /// Distribution of the rows along `y`
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Returns the distribution for the `function` values of a grid of `width` columns, row by
+    /// row.
+    pub fn new(function: &[f64], width: usize) -> Self {
+        assert!(
+            width > 0 && !function.is_empty() && function.len().is_multiple_of(width),
+            "function values should form a grid"
+        );
+
+        let rows: Vec<Distribution1D> = function
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+
+        Self { rows, marginal }
+    }
+
+    /// Returns the integral of the function over `[0, 1)^2`, the average of its values.
+    pub fn integral(&self) -> f64 {
+        self.marginal.integral()
+    }
+
+    /// Maps `u` to a point in `[0, 1)^2` and returns it with its density.
+    pub fn sample(&self, u: (f64, f64)) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample(u.1);
+        let (x, pdf_x, _) = self.rows[row].sample(u.0);
+
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// Returns the density of sampling `(x, y)`.
+    pub fn pdf(&self, (x, y): (f64, f64)) -> f64 {
+        let row = ((y * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        self.marginal.pdf(y) * self.rows[row].pdf(x)
+    }
+}
+
+/// Returns the weight of a sample with density `f_pdf` when it is combined with a strategy with
+/// density `g_pdf` (power heuristic with exponent 2 of Veach).
+pub fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let (f, g) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    match f.is_infinite() {
+        true => 1.0,
+        false if f + g > 0.0 => f / (f + g),
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a grid of `n` by `n` samples in the unit square.
+    fn grid(n: usize) -> impl Iterator<Item = (f64, f64)> {
+        (0..n * n).map(move |i| {
+            (
+                ((i % n) as f64 + 0.5) / n as f64,
+                ((i / n) as f64 + 0.5) / n as f64,
+            )
+        })
+    }
+
+    #[test]
+    fn disk_samples_cover_unit_disk() {
+        let n = 64;
+        let mut quadrants = [0; 4];
+        for u in grid(n) {
+            let p = sample_uniform_disk_concentric(u);
+            assert!(p.length() <= 1.0 + 1e-12 && p.z() == 0.0);
+            quadrants[usize::from(p.x() < 0.0) * 2 + usize::from(p.y() < 0.0)] += 1;
+        }
+        assert_eq!(quadrants, [n * n / 4; 4]);
+        assert_eq!(sample_uniform_disk_concentric((0.5, 0.5)), Vec3::default());
+    }
+
+    #[test]
+    fn sphere_and_ball_samples() {
+        let n = 32;
+        let mut mean = Vec3::default();
+        for u in grid(n) {
+            let p = sample_uniform_sphere(u);
+            assert!((p.length() - 1.0).abs() < 1e-12);
+            mean += p / (n * n) as f64;
+
+            let w = (u.0 + u.1) / 2.0;
+            assert!((sample_uniform_ball(u, w).length() - w.cbrt()).abs() < 1e-12);
+        }
+        assert!(mean.length() < 1e-3);
+    }
+
+    #[test]
+    fn distribution_1d_sample_matches_pdf() {
+        let distribution = Distribution1D::new(vec![1.0, 0.0, 3.0, 4.0]);
+        assert!((distribution.integral() - 2.0).abs() < 1e-12);
+
+        let total: f64 = (0..4).map(|i| distribution.interval_probability(i)).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+
+        for i in 0..100 {
+            let u = i as f64 / 100.0;
+            let (x, pdf, index) = distribution.sample(u);
+            assert!((0.0..1.0).contains(&x));
+            assert_eq!(index, (x * 4.0) as usize);
+            assert_ne!(index, 1, "interval without density was sampled");
+            assert!((pdf - distribution.pdf(x)).abs() < 1e-12);
+        }
+
+        // the sampled point is the inverse of the cdf
+        let (x, _, _) = distribution.sample(0.75);
+        assert!((x - 0.875).abs() < 1e-12);
+    }
+
+    #[test]
+    fn distribution_1d_zero_function_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 3]);
+        let (x, pdf, index) = distribution.sample(0.5);
+        assert!((x - 0.5).abs() < 1e-12);
+        assert_eq!((pdf, index), (1.0, 1));
+    }
+
+    #[test]
+    fn distribution_2d_sample_matches_pdf() {
+        let function = [1.0, 2.0, 0.0, 4.0, 0.5, 0.0, 3.0, 1.0, 2.0, 0.0, 6.0, 1.0];
+        let distribution = Distribution2D::new(&function, 4);
+
+        let n = 32;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let ((x, y), pdf) = distribution.sample(u);
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                assert!(pdf > 0.0);
+                assert!((pdf - distribution.pdf((x, y))).abs() < 1e-9);
+            }
+        }
+
+        // the density is proportional to the function and integrates to one
+        let integral: f64 = function.iter().sum::<f64>() / function.len() as f64;
+        let mut total = 0.0;
+        for (k, value) in function.iter().enumerate() {
+            let center = (
+                (k % 4) as f64 / 4.0 + 0.125,
+                (k / 4) as f64 / 3.0 + 1.0 / 6.0,
+            );
+            let pdf = distribution.pdf(center);
+            assert!((pdf - value / integral).abs() < 1e-9);
+            total += pdf / function.len() as f64;
+        }
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn power_heuristic_weights() {
+        assert_eq!(power_heuristic(f64::INFINITY, 1.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        let (f, g) = (0.3, 1.7);
+        assert!((power_heuristic(f, g) + power_heuristic(g, f) - 1.0).abs() < 1e-12);
+    }
+}
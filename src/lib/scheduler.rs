@@ -0,0 +1,256 @@
+use std::str::FromStr;
+
+/// Rectangle of pixels in image coordinates, where `(0, 0)` is the top left corner of the image.
+/// The upper bounds are exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Region {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        assert!(x0 <= x1 && y0 <= y1, "region has a negative size");
+        Self { x0, y0, x1, y1 }
+    }
+
+    /// Returns the region covering a whole image.
+    pub fn full(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    /// Returns `true` if the region lies completely within `other`.
+    pub fn inside(&self, other: &Self) -> bool {
+        self.x0 >= other.x0 && self.y0 >= other.y0 && self.x1 <= other.x1 && self.y1 <= other.y1
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses a region written as `x0,y0,x1,y1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bounds = s
+            .split(',')
+            .map(|bound| bound.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        match bounds[..] {
+            [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(Self::new(x0, y0, x1, y1)),
+            [_, _, _, _] => Err("region should have `x0 < x1` and `y0 < y1`".to_string()),
+            _ => Err("region should be written as `x0,y0,x1,y1`".to_string()),
+        }
+    }
+}
+
+/// Order in which the tiles of an image are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Row by row, starting at the top left tile.
+    Scanline,
+    /// Spiraling outwards from the center tile, so the center of the image is rendered first.
+    Spiral,
+    /// Following a Hilbert curve (<https://en.wikipedia.org/wiki/Hilbert_curve>), which keeps
+    /// successive tiles close to each other.
+    Hilbert,
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanline" => Ok(Self::Scanline),
+            "spiral" => Ok(Self::Spiral),
+            "hilbert" => Ok(Self::Hilbert),
+            _ => Err(format!(
+                "unknown tile order `{}` (expected `scanline`, `spiral` or `hilbert`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Splits the region of the image that is rendered into square tiles and decides the order in
+/// which they are rendered.
+///
+/// The tile grid and its order are laid out over the whole image and then clipped to the region.
+/// Rendering a region therefore visits its pixels in the same order as a full render, so samples
+/// that are splatted over multiple pixels are added up in the same order as well.
+pub struct TileScheduler {
+    tiles: Vec<Region>,
+}
+
+impl TileScheduler {
+    /// Returns a new instance of the `TileScheduler` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `width`: Width of the image.
+    /// * `height`: Height of the image.
+    /// * `region`: Part of the image that is rendered.
+    /// * `tile_size`: Width and height of a tile, tiles at the border of the region can be smaller.
+    /// * `order`: Order in which the tiles are rendered.
+    pub fn new(
+        width: usize,
+        height: usize,
+        region: Region,
+        tile_size: usize,
+        order: TileOrder,
+    ) -> Self {
+        assert!(tile_size > 0, "tile size should be at least 1");
+
+        let columns = width.div_ceil(tile_size);
+        let rows = height.div_ceil(tile_size);
+
+        let tiles = match order {
+            TileOrder::Scanline => scanline_order(columns, rows),
+            TileOrder::Spiral => spiral_order(columns, rows),
+            TileOrder::Hilbert => hilbert_order(columns, rows),
+        }
+        .into_iter()
+        .filter_map(|(column, row)| {
+            // clip the tile to the region
+            let x0 = (column * tile_size).max(region.x0);
+            let y0 = (row * tile_size).max(region.y0);
+            let x1 = ((column + 1) * tile_size).min(region.x1);
+            let y1 = ((row + 1) * tile_size).min(region.y1);
+
+            (x0 < x1 && y0 < y1).then(|| Region::new(x0, y0, x1, y1))
+        })
+        .collect();
+
+        Self { tiles }
+    }
+
+    pub fn tiles(&self) -> &[Region] {
+        &self.tiles
+    }
+}
+
+fn scanline_order(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect()
+}
+
+fn spiral_order(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let mut order = Vec::with_capacity(columns * rows);
+    let (mut x, mut y) = ((columns as isize - 1) / 2, (rows as isize - 1) / 2);
+    let mut direction = (1, 0);
+    let mut leg_length = 1;
+
+    // walk legs of growing length (1, 1, 2, 2, 3, 3, ...) and keep the cells inside the grid
+    while order.len() < columns * rows {
+        for _ in 0..2 {
+            for _ in 0..leg_length {
+                if (0..columns as isize).contains(&x) && (0..rows as isize).contains(&y) {
+                    order.push((x as usize, y as usize));
+                }
+                x += direction.0;
+                y += direction.1;
+            }
+            direction = (-direction.1, direction.0);
+        }
+        leg_length += 1;
+    }
+
+    order
+}
+
+fn hilbert_order(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let n = columns.max(rows).next_power_of_two();
+
+    (0..n * n)
+        .map(|d| hilbert_position(n, d))
+        .filter(|&(x, y)| x < columns && y < rows)
+        .collect()
+}
+
+/// Returns the position of the `d`-th cell on the Hilbert curve that fills a `n * n` grid.
+fn hilbert_position(n: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+
+        // rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_region() {
+        assert_eq!("1,2,30,40".parse(), Ok(Region::new(1, 2, 30, 40)));
+        assert_eq!(" 0, 0 ,8,6".parse(), Ok(Region::full(8, 6)));
+
+        for region in [
+            "3,0,1,4",
+            "0,4,2,4",
+            "1,2,3",
+            "1,2,3,4,5",
+            "a,b,c,d",
+            "-1,0,2,2",
+            "",
+        ] {
+            assert!(region.parse::<Region>().is_err(), "{}", region);
+        }
+    }
+
+    #[test]
+    fn region_size() {
+        let region = Region::new(2, 1, 6, 4);
+        assert_eq!((region.width(), region.height(), region.area()), (4, 3, 12));
+        assert!(region.contains(2, 1) && region.contains(5, 3));
+        assert!(!region.contains(6, 3) && !region.contains(5, 4));
+        assert!(region.inside(&Region::full(6, 4)));
+        assert!(!region.inside(&Region::full(5, 4)));
+    }
+
+    #[test]
+    fn parse_tile_order() {
+        assert_eq!("scanline".parse(), Ok(TileOrder::Scanline));
+        assert_eq!("spiral".parse(), Ok(TileOrder::Spiral));
+        assert_eq!("hilbert".parse(), Ok(TileOrder::Hilbert));
+        assert!("random".parse::<TileOrder>().is_err());
+    }
+}
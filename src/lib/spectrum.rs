@@ -0,0 +1,534 @@
+use super::{Color, Vec3};
+use std::{cmp::Ordering, sync::OnceLock};
+
+/// Shortest wavelength (in nm) that is rendered in spectral mode.
+pub const LAMBDA_MIN: f64 = 360.0;
+/// Longest wavelength (in nm) that is rendered in spectral mode.
+pub const LAMBDA_MAX: f64 = 830.0;
+
+/// Wavelength of the Fraunhofer d line (in nm), where refraction indices are usually specified.
+const LAMBDA_D_LINE: f64 = 587.56;
+
+/// Number of wavelengths every path carries.
+const WAVELENGTHS_PER_PATH: usize = 3;
+
+/// Step (in nm) between the wavelengths used to integrate a spectrum against the color matching
+/// functions.
+const INTEGRATION_STEP: f64 = 5.0;
+
+/// Maximal number of Gauss-Newton iterations to fit the spectrum of a single color.
+const MAX_FIT_ITERATIONS: usize = 50;
+
+/// Resolution of every dimension of the RGB to spectrum table.
+const TABLE_RESOLUTION: usize = 32;
+
+/// White point of D65 in XYZ, the white of sRGB.
+const D65_WHITE: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Converts XYZ to linear sRGB (IEC 61966-2-1).
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266_0, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+/// The wavelengths (in nm) a path carries in spectral mode, with the density they were sampled
+/// with. The first one is the hero wavelength, the others are spread evenly over the visible
+/// range so the three of them cover the spectrum.
+///
+/// Values of a path (radiance, attenuation) are stored in a `Color`, where the channels hold the
+/// values at the three wavelengths instead of red, green and blue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [f64; WAVELENGTHS_PER_PATH],
+    pdf: [f64; WAVELENGTHS_PER_PATH],
+}
+
+impl SampledWavelengths {
+    /// Samples wavelengths with a density that roughly follows the sensitivity of the eye
+    /// (Radziszewski et al., "An Improved Technique for Full Spectral Rendering"), using a single
+    /// sample `u` that is rotated for the other wavelengths.
+    pub fn sample_visible(u: f64) -> Self {
+        let mut lambda = [0.0; WAVELENGTHS_PER_PATH];
+        let mut pdf = [0.0; WAVELENGTHS_PER_PATH];
+
+        for (i, (lambda, pdf)) in lambda.iter_mut().zip(&mut pdf).enumerate() {
+            let u = (u + i as f64 / WAVELENGTHS_PER_PATH as f64).fract();
+            *lambda = 538.0 - 138.888_889 * (0.856_910_62 - 1.827_501_97 * u).atanh();
+            *pdf = match (LAMBDA_MIN..=LAMBDA_MAX).contains(lambda) {
+                true => 0.003_939_804_2 / (0.0072 * (*lambda - 538.0)).cosh().powi(2),
+                false => 0.0,
+            };
+        }
+
+        Self { lambda, pdf }
+    }
+
+    /// Returns the hero wavelength, the one that is kept by `terminate_secondary`.
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn lambda(&self) -> [f64; WAVELENGTHS_PER_PATH] {
+        self.lambda
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+
+    /// Drops all wavelengths except the hero wavelength, which is needed once a path takes a
+    /// direction that only exists for a single wavelength (e.g. refraction with dispersion).
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+
+        self.pdf[1..].iter_mut().for_each(|pdf| *pdf = 0.0);
+        self.pdf[0] /= WAVELENGTHS_PER_PATH as f64;
+    }
+
+    /// Returns the values of the RGB `color` at the wavelengths, see `SigmoidSpectrum`.
+    pub fn upsample(&self, color: Color) -> Color {
+        let spectrum = SigmoidSpectrum::from_rgb(color);
+        let [a, b, c] = self.lambda.map(|lambda| spectrum.evaluate(lambda));
+        Color::new(a, b, c)
+    }
+
+    /// Returns the XYZ color of a path that carries the radiance `values` at the wavelengths.
+    pub fn xyz(&self, values: Color) -> Color {
+        let tables = tables();
+        let values = [values.red(), values.green(), values.blue()];
+
+        let mut xyz = [0.0; 3];
+        for ((&lambda, &pdf), value) in self.lambda.iter().zip(&self.pdf).zip(values) {
+            if pdf == 0.0 {
+                continue;
+            }
+
+            let cmf = color_matching_functions(lambda);
+            for (xyz, cmf) in xyz.iter_mut().zip(cmf) {
+                *xyz += value * cmf / (pdf * tables.y_integral);
+            }
+        }
+
+        let [x, y, z] = xyz.map(|v| (v / WAVELENGTHS_PER_PATH as f64).max(0.0));
+        Color::new(x, y, z)
+    }
+}
+
+/// Returns the linear sRGB color of `xyz`. The white of an equal energy spectrum is mapped to the
+/// white of sRGB (scaling of the XYZ values), so white surfaces under a white sky stay white.
+/// Colors outside of the gamut of sRGB have negative channels.
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    let white = tables().white_balance;
+    let xyz = [xyz.x() * white[0], xyz.y() * white[1], xyz.z() * white[2]];
+    let [r, g, b] = XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2]);
+    Vec3::new(r, g, b)
+}
+
+/// Returns the linear sRGB color of `xyz` without white balance, for colors that are already
+/// relative to the D65 white of sRGB (e.g. measured daylight).
+pub fn colorimetric_xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    let xyz = [xyz.x(), xyz.y(), xyz.z()];
+    let [r, g, b] = XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2]);
+    Vec3::new(r, g, b)
+}
+
+/// Smooth spectrum that reproduces an RGB color, after Jakob and Hanika ("A Low-Dimensional
+/// Function Space for Efficient Spectral Upsampling"). The spectrum is a sigmoid of a quadratic
+/// polynomial, so it is bounded by `scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SigmoidSpectrum {
+    /// Coefficients of the polynomial in the wavelength normalized to `[0, 1]`
+    coefficients: [f64; 3],
+    scale: f64,
+}
+
+impl SigmoidSpectrum {
+    /// Returns the spectrum of `color`. Colors with a channel above 1 (e.g. of light sources) are
+    /// scaled down to fit and scaled back up by the spectrum.
+    pub fn from_rgb(color: Color) -> Self {
+        let rgb = [color.red(), color.green(), color.blue()];
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+
+        let scale = match max <= 1.0 {
+            true => 1.0,
+            false => 2.0 * max,
+        };
+        Self {
+            coefficients: tables().coefficients(rgb.map(|c| c / scale)),
+            scale,
+        }
+    }
+
+    pub fn evaluate(&self, lambda: f64) -> f64 {
+        let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+        let [c0, c1, c2] = self.coefficients;
+        self.scale * sigmoid((c0 * t + c1) * t + c2)
+    }
+}
+
+/// Refraction index of a dielectric, which can depend on the wavelength (dispersion).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    Constant(f64),
+    /// Cauchy's equation `n = a + b / lambda^2`, with the wavelength in micrometers.
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// Sellmeier equation `n^2 = 1 + sum(b_i lambda^2 / (lambda^2 - c_i))`, with the wavelength
+    /// in micrometers.
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Ior {
+    /// Borosilicate crown glass (Schott N-BK7).
+    pub fn bk7() -> Self {
+        Self::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        }
+    }
+
+    pub fn fused_silica() -> Self {
+        Self::Sellmeier {
+            b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+            c: [0.004_679_148, 0.013_512_063, 97.934_002_5],
+        }
+    }
+
+    /// Diamond, which has a strong dispersion that causes its "fire".
+    pub fn diamond() -> Self {
+        Self::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030_625, 0.011_236, 0.0],
+        }
+    }
+
+    /// Returns the refraction index at wavelength `lambda` (in nm).
+    pub fn at(&self, lambda: f64) -> f64 {
+        let micrometers = lambda / 1000.0;
+        let l2 = micrometers * micrometers;
+
+        match self {
+            Self::Constant(n) => *n,
+            Self::Cauchy { a, b } => a + b / l2,
+            Self::Sellmeier { b, c } => {
+                (1.0 + b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum::<f64>()).sqrt()
+            }
+        }
+    }
+
+    /// Returns the refraction index at the d line, used when rendering in RGB.
+    pub fn d_line(&self) -> f64 {
+        self.at(LAMBDA_D_LINE)
+    }
+
+    pub fn dispersive(&self) -> bool {
+        !matches!(self, Self::Constant(_))
+    }
+}
+
+/// Returns the CIE 1931 color matching functions `[x, y, z]` at wavelength `lambda` (in nm),
+/// using the multi-lobe fit of Wyman et al. ("Simple Analytic Approximations to the CIE XYZ Color
+/// Matching Functions").
+pub fn color_matching_functions(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+    .map(|v: f64| v.max(0.0))
+}
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+/// Data that is shared by all conversions between spectra and colors, computed on first use.
+struct SpectralTables {
+    /// Integral of the `y` color matching function, so a constant spectrum of 1 has `Y = 1`
+    y_integral: f64,
+    /// Scales XYZ so an equal energy spectrum maps to the sRGB white
+    white_balance: [f64; 3],
+    /// Linear sRGB contribution of every integration wavelength
+    wavelength_rgb: Vec<(f64, [f64; 3])>,
+    /// `z` (largest channel) values of the table nodes
+    scale: Vec<f64>,
+    /// Sigmoid coefficients, indexed by the largest channel, `z`, `y` and `x`
+    coefficients: Vec<[f64; 3]>,
+}
+
+fn tables() -> &'static SpectralTables {
+    static TABLES: OnceLock<SpectralTables> = OnceLock::new();
+    TABLES.get_or_init(SpectralTables::new)
+}
+
+impl SpectralTables {
+    fn new() -> Self {
+        let steps = ((LAMBDA_MAX - LAMBDA_MIN) / INTEGRATION_STEP) as usize;
+        let wavelengths: Vec<f64> = (0..=steps)
+            .map(|i| LAMBDA_MIN + i as f64 * INTEGRATION_STEP)
+            .collect();
+
+        let mut integrals = [0.0; 3];
+        for &lambda in &wavelengths {
+            for (integral, cmf) in integrals.iter_mut().zip(color_matching_functions(lambda)) {
+                *integral += cmf * INTEGRATION_STEP;
+            }
+        }
+        let y_integral = integrals[1];
+        let white_balance = [
+            D65_WHITE[0] * y_integral / integrals[0],
+            D65_WHITE[1],
+            D65_WHITE[2] * y_integral / integrals[2],
+        ];
+
+        let mut tables = Self {
+            y_integral,
+            white_balance,
+            wavelength_rgb: Vec::new(),
+            scale: Vec::new(),
+            coefficients: Vec::new(),
+        };
+
+        tables.wavelength_rgb = wavelengths
+            .iter()
+            .map(|&lambda| {
+                let [x, y, z] =
+                    color_matching_functions(lambda).map(|cmf| cmf * INTEGRATION_STEP / y_integral);
+                let rgb = tables.xyz_to_rgb([x, y, z]);
+                ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN), rgb)
+            })
+            .collect();
+        tables.build_coefficients();
+
+        tables
+    }
+
+    fn xyz_to_rgb(&self, xyz: [f64; 3]) -> [f64; 3] {
+        let xyz = [0, 1, 2].map(|i| xyz[i] * self.white_balance[i]);
+        XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2])
+    }
+
+    /// Fits the coefficients for every node of the table. Every fit starts from the solution of
+    /// a neighbor, which keeps the iterations away from the flat regions of the sigmoid: in the
+    /// middle `z` plane from gray (`x = y = 1`) towards the saturated colors, and from there
+    /// towards the darkest and brightest planes.
+    fn build_coefficients(&mut self) {
+        let n = TABLE_RESOLUTION;
+        let smoothstep = |x: f64| x * x * (3.0 - 2.0 * x);
+        self.scale = (0..n)
+            .map(|k| smoothstep(smoothstep(k as f64 / (n - 1) as f64)))
+            .collect();
+        self.coefficients = vec![[0.0; 3]; 3 * n * n * n];
+
+        let index = |l: usize, k: usize, j: usize, i: usize| ((l * n + k) * n + j) * n + i;
+        let middle = n / 2;
+        let planes = std::iter::once(middle)
+            .chain(middle + 1..n)
+            .chain((0..middle).rev());
+
+        for l in 0..3 {
+            for k in planes.clone() {
+                let z = self.scale[k];
+                for j in (0..n).rev() {
+                    let y = j as f64 / (n - 1) as f64;
+                    for i in (0..n).rev() {
+                        let x = i as f64 / (n - 1) as f64;
+
+                        let neighbor = match k.cmp(&middle) {
+                            Ordering::Greater => index(l, k - 1, j, i),
+                            Ordering::Less => index(l, k + 1, j, i),
+                            Ordering::Equal if i < n - 1 => index(l, k, j, i + 1),
+                            Ordering::Equal if j < n - 1 => index(l, k, j + 1, i),
+                            // gray, which is reached from any start
+                            Ordering::Equal => index(l, k, j, i),
+                        };
+                        let mut coefficients = self.coefficients[neighbor];
+
+                        let mut rgb = [0.0; 3];
+                        rgb[l] = z;
+                        rgb[(l + 1) % 3] = x * z;
+                        rgb[(l + 2) % 3] = y * z;
+
+                        self.fit(rgb, &mut coefficients);
+                        self.coefficients[index(l, k, j, i)] = coefficients;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Refines `coefficients` with Gauss-Newton iterations until their spectrum has color `rgb`.
+    /// Steps are halved until they reduce the error, as colors at the border of the gamut can
+    /// only be approached with steep spectra.
+    fn fit(&self, rgb: [f64; 3], coefficients: &mut [f64; 3]) {
+        let error = |residual: [f64; 3]| residual.iter().map(|r| r * r).sum::<f64>();
+        let (mut residual, mut jacobian) = self.residual(rgb, *coefficients);
+
+        for _ in 0..MAX_FIT_ITERATIONS {
+            if error(residual) < 1.0e-12 {
+                break;
+            }
+            let Some(step) = solve(jacobian, residual) else {
+                break;
+            };
+
+            let mut step_size = 1.0;
+            let improved = loop {
+                let candidate = [0, 1, 2].map(|i| coefficients[i] - step_size * step[i]);
+                let (candidate_residual, candidate_jacobian) = self.residual(rgb, candidate);
+                if error(candidate_residual) < error(residual) {
+                    *coefficients = candidate;
+                    (residual, jacobian) = (candidate_residual, candidate_jacobian);
+                    break true;
+                }
+
+                step_size /= 2.0;
+                if step_size < 1.0e-4 {
+                    break false;
+                }
+            };
+            if !improved {
+                break;
+            }
+        }
+    }
+
+    /// Returns the difference between the color of the spectrum with `coefficients` and `rgb`,
+    /// and its derivatives with respect to the coefficients.
+    fn residual(&self, rgb: [f64; 3], coefficients: [f64; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+        let mut residual = rgb.map(|c| -c);
+        let mut jacobian = [[0.0; 3]; 3];
+
+        for &(t, weights) in &self.wavelength_rgb {
+            let x = (coefficients[0] * t + coefficients[1]) * t + coefficients[2];
+            let s = sigmoid(x);
+            let ds = 0.5 / (1.0 + x * x).powf(1.5);
+
+            for c in 0..3 {
+                residual[c] += s * weights[c];
+                for (p, power) in [t * t, t, 1.0].into_iter().enumerate() {
+                    jacobian[c][p] += ds * power * weights[c];
+                }
+            }
+        }
+
+        (residual, jacobian)
+    }
+
+    /// Returns the coefficients of the spectrum of `rgb` (with channels in `[0, 1]`), interpolated
+    /// from the table.
+    fn coefficients(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let rgb = rgb.map(|c| c.clamp(0.0, 1.0));
+        if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+            // inverse of the sigmoid for constant spectra
+            let v = rgb[0];
+            return [0.0, 0.0, (v - 0.5) / (v * (1.0 - v)).sqrt()];
+        }
+
+        let n = TABLE_RESOLUTION;
+        let l = match (rgb[0] >= rgb[1], rgb[1] >= rgb[2], rgb[0] >= rgb[2]) {
+            (true, _, true) => 0,
+            (false, true, _) => 1,
+            _ => 2,
+        };
+        let z = rgb[l];
+        let x = rgb[(l + 1) % 3] * (n - 1) as f64 / z;
+        let y = rgb[(l + 2) % 3] * (n - 1) as f64 / z;
+
+        let xi = (x as usize).min(n - 2);
+        let yi = (y as usize).min(n - 2);
+        let zi = self.scale[..n - 1]
+            .iter()
+            .rposition(|&scale| scale <= z)
+            .unwrap_or(0);
+        let (dx, dy) = (x - xi as f64, y - yi as f64);
+        let dz = (z - self.scale[zi]) / (self.scale[zi + 1] - self.scale[zi]);
+
+        let node = |k: usize, j: usize, i: usize| self.coefficients[((l * n + k) * n + j) * n + i];
+        let mut coefficients = [0.0; 3];
+        for (c, coefficient) in coefficients.iter_mut().enumerate() {
+            let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+            let plane = |k: usize| {
+                lerp(
+                    lerp(node(k, yi, xi)[c], node(k, yi, xi + 1)[c], dx),
+                    lerp(node(k, yi + 1, xi)[c], node(k, yi + 1, xi + 1)[c], dx),
+                    dy,
+                )
+            };
+            *coefficient = lerp(plane(zi), plane(zi + 1), dz);
+        }
+
+        coefficients
+    }
+}
+
+/// Solves the linear system `a x = b` with Cramer's rule, or returns `None` if it is singular.
+fn solve(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let determinant = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let d = determinant(a);
+    if d.abs() < 1.0e-15 {
+        return None;
+    }
+
+    Some([0, 1, 2].map(|column| {
+        let mut m = a;
+        for row in 0..3 {
+            m[row][column] = b[row];
+        }
+        determinant(m) / d
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sellmeier_dispersion() {
+        // refraction indices of N-BK7 from the Schott data sheet
+        let bk7 = Ior::bk7();
+        assert!((bk7.d_line() - 1.5168).abs() < 1e-4);
+        assert!((bk7.at(486.1) - 1.5224).abs() < 1e-4);
+        assert!((bk7.at(656.3) - 1.5143).abs() < 1e-4);
+        assert!(bk7.dispersive());
+
+        // normal dispersion: the index falls with the wavelength
+        for ior in [Ior::bk7(), Ior::fused_silica(), Ior::diamond()] {
+            assert!(ior.at(400.0) > ior.d_line() && ior.d_line() > ior.at(700.0));
+        }
+        assert!((Ior::diamond().d_line() - 2.417).abs() < 2e-3);
+    }
+
+    #[test]
+    fn constant_ior() {
+        let ior = Ior::Constant(1.33);
+        assert_eq!(ior.at(400.0), 1.33);
+        assert_eq!(ior.d_line(), 1.33);
+        assert!(!ior.dispersive());
+    }
+}
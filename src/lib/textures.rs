@@ -0,0 +1,96 @@
+use super::{
+    display::TransferFunction,
+    image::Image,
+    traits::{FloatTexture, Texture},
+    Color, Point3,
+};
+use std::{io, path::Path, rc::Rc};
+
+/// A plain color is a texture with the same value everywhere.
+impl Texture for Color {
+    fn value(&self, _uv: (f64, f64), _p: Point3) -> Color {
+        *self
+    }
+}
+
+/// A plain number is a texture with the same value everywhere.
+impl FloatTexture for f64 {
+    fn value(&self, _uv: (f64, f64), _p: Point3) -> f64 {
+        *self
+    }
+}
+
+/// Three dimensional checker pattern of cubes that alternate between two textures, either color
+/// (`CheckerTexture<dyn Texture>`) or scalar (`CheckerTexture<dyn FloatTexture>`) textures.
+pub struct CheckerTexture<T: ?Sized> {
+    /// Size of the cubes
+    scale: f64,
+    even: Rc<T>,
+    odd: Rc<T>,
+}
+
+impl<T: ?Sized> CheckerTexture<T> {
+    pub fn new(scale: f64, even: Rc<T>, odd: Rc<T>) -> Self {
+        assert!(scale > 0.0, "checker scale should be larger than 0");
+        Self { scale, even, odd }
+    }
+
+    /// Returns the texture of the cube that contains `p`.
+    fn select(&self, p: Point3) -> &T {
+        let cell = (p.x() / self.scale).floor()
+            + (p.y() / self.scale).floor()
+            + (p.z() / self.scale).floor();
+
+        match cell.rem_euclid(2.0) < 1.0 {
+            true => &self.even,
+            false => &self.odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture<dyn Texture> {
+    fn value(&self, uv: (f64, f64), p: Point3) -> Color {
+        self.select(p).value(uv, p)
+    }
+}
+
+impl FloatTexture for CheckerTexture<dyn FloatTexture> {
+    fn value(&self, uv: (f64, f64), p: Point3) -> f64 {
+        self.select(p).value(uv, p)
+    }
+}
+
+/// Texture that maps an image onto the surface coordinates, see `Image::lookup`. As scalar
+/// texture it returns the mean of the color channels.
+pub struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Self {
+        Self { image }
+    }
+
+    /// Loads a PPM image with sRGB encoded colors, e.g. a base color map.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Image::read_ppm(path, TransferFunction::Srgb).map(Self::new)
+    }
+
+    /// Loads a PPM image with linear values, e.g. a roughness or metallic map.
+    pub fn open_linear(path: &Path) -> io::Result<Self> {
+        Image::read_ppm(path, TransferFunction::Linear).map(Self::new)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: (f64, f64), _p: Point3) -> Color {
+        self.image.lookup(uv)
+    }
+}
+
+impl FloatTexture for ImageTexture {
+    fn value(&self, uv: (f64, f64), _p: Point3) -> f64 {
+        let color = self.image.lookup(uv);
+        (color.red() + color.green() + color.blue()) / 3.0
+    }
+}
@@ -0,0 +1,226 @@
+use super::{
+    spectrum::{self, SampledWavelengths, SigmoidSpectrum},
+    traits::FloatTexture,
+    Color, HitRecord, Vec3,
+};
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+    rc::Rc,
+};
+
+/// Shortest wavelength (in nm) used to integrate the reflectance of a film in RGB mode.
+const RGB_LAMBDA_MIN: f64 = 380.0;
+/// Longest wavelength (in nm) used to integrate the reflectance of a film in RGB mode.
+const RGB_LAMBDA_MAX: f64 = 780.0;
+/// Step (in nm) between the wavelengths used to integrate the reflectance in RGB mode.
+const RGB_LAMBDA_STEP: f64 = 10.0;
+
+/// Thin transparent layer on top of a surface (e.g. a soap film, oil slick or anti-reflective
+/// lens coating). Light reflected at the top and the bottom of the film interferes, which
+/// makes the reflectance depend on the wavelength, the viewing angle and the thickness of the
+/// film (iridescence).
+///
+/// The reflectance is the exact Airy summation over all reflections inside the film, for a
+/// substrate with a real (dielectric) or complex (metal) refraction index.
+pub struct ThinFilm {
+    /// Thickness of the film in nanometers
+    thickness: Rc<dyn FloatTexture>,
+    /// Refraction index of the film
+    ior: f64,
+}
+
+impl ThinFilm {
+    /// Returns a new instance of the `ThinFilm` struct.
+    ///
+    /// Arguments:
+    ///
+    /// * `thickness`: Thickness of the film in nanometers, interference colors show up for
+    ///   thicknesses around 100 to 1000 nm.
+    /// * `ior`: Refraction index of the film, e.g. `1.33` for a soap film.
+    pub fn new(thickness: Rc<dyn FloatTexture>, ior: f64) -> Self {
+        assert!(ior > 0.0, "refraction index should be larger than 0");
+        Self { thickness, ior }
+    }
+
+    /// Returns the reflectance of the film at `hitrecord`, in RGB or, with `wavelengths`, at the
+    /// wavelengths of the path.
+    ///
+    /// Arguments:
+    ///
+    /// * `cos_i`: Cosine of the angle between the incoming direction and the normal.
+    /// * `incident_ior`: Refraction index of the medium the light comes from.
+    /// * `substrate`: Returns the complex refraction index `(eta, k)` of the material below the
+    ///   film at a wavelength (in nm).
+    pub fn reflectance(
+        &self,
+        hitrecord: &HitRecord,
+        cos_i: f64,
+        incident_ior: f64,
+        substrate: impl Fn(f64) -> (f64, f64),
+        wavelengths: Option<&SampledWavelengths>,
+    ) -> Color {
+        let thickness = self
+            .thickness
+            .value(hitrecord.uv(), hitrecord.get_inpact_point())
+            .max(0.0);
+        let reflectance = |lambda: f64| {
+            let (eta, k) = substrate(lambda);
+            airy_reflectance(
+                cos_i,
+                incident_ior,
+                self.ior,
+                Complex::new(eta, k),
+                thickness,
+                lambda,
+            )
+        };
+
+        if let Some(wavelengths) = wavelengths {
+            let [a, b, c] = wavelengths.lambda().map(reflectance);
+            return Color::new(a, b, c);
+        }
+
+        // project the reflectance spectrum to RGB, weighted like an equal energy spectrum so
+        // a constant reflectance keeps its value
+        let mut xyz = Vec3::default();
+        let mut y_integral = 0.0;
+        let mut lambda = RGB_LAMBDA_MIN;
+        while lambda <= RGB_LAMBDA_MAX {
+            let [x, y, z] = spectrum::color_matching_functions(lambda);
+            xyz += reflectance(lambda) * Vec3::new(x, y, z);
+            y_integral += y;
+            lambda += RGB_LAMBDA_STEP;
+        }
+
+        let rgb = spectrum::xyz_to_linear_srgb(xyz / y_integral);
+        Color::new(
+            rgb.x().clamp(0.0, 1.0),
+            rgb.y().clamp(0.0, 1.0),
+            rgb.z().clamp(0.0, 1.0),
+        )
+    }
+}
+
+/// Returns the complex refraction index `(eta, k)` of a metal with the reflectance `albedo` at
+/// normal incidence, using the edge tint mapping of Gulbrandsen ("Artist Friendly Metallic
+/// Fresnel") with the edge tint set to the reflectance. The albedo is upsampled to a spectrum, so
+/// the index can be evaluated at any wavelength (in nm).
+pub fn metal_ior(albedo: Color) -> impl Fn(f64) -> (f64, f64) {
+    let spectrum = SigmoidSpectrum::from_rgb(albedo);
+
+    move |lambda| {
+        let r = spectrum.evaluate(lambda).clamp(0.0, 0.999);
+        let sqrt_r = r.sqrt();
+        let eta = r * (1.0 - r) / (1.0 + r) + (1.0 - r) * (1.0 + sqrt_r) / (1.0 - sqrt_r);
+        let k = ((r * (eta + 1.0).powi(2) - (eta - 1.0).powi(2)) / (1.0 - r))
+            .max(0.0)
+            .sqrt();
+        (eta, k)
+    }
+}
+
+/// Returns the reflectance of unpolarized light of wavelength `lambda` (in nm) at a film of
+/// `thickness` nm with refraction index `n1` between a medium with index `n0` and a substrate
+/// with complex index `n2`.
+fn airy_reflectance(cos_i: f64, n0: f64, n1: f64, n2: Complex, thickness: f64, lambda: f64) -> f64 {
+    let cos0 = Complex::new(cos_i.clamp(0.0, 1.0), 0.0);
+    let sin2 = n0 * n0 * (1.0 - cos_i * cos_i).max(0.0);
+    let n0 = Complex::new(n0, 0.0);
+    let n1 = Complex::new(n1, 0.0);
+
+    // cosines of the angles in the film and the substrate (Snell's law), complex for total
+    // internal reflection and absorbing substrates
+    let one = Complex::new(1.0, 0.0);
+    let cos1 = (one - Complex::new(sin2, 0.0) / (n1 * n1)).sqrt();
+    let cos2 = (one - Complex::new(sin2, 0.0) / (n2 * n2)).sqrt();
+
+    // phase difference between successive reflections in the film
+    let phase = Complex::new(0.0, 4.0 * PI * thickness / lambda) * n1 * cos1;
+    let delay = phase.exp();
+
+    let airy = |r01: Complex, r12: Complex| {
+        let r = (r01 + r12 * delay) / (one + r01 * r12 * delay);
+        r.norm_squared()
+    };
+
+    let s = airy(
+        (n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1),
+        (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+    );
+    let p = airy(
+        (n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1),
+        (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+    );
+
+    ((s + p) / 2.0).clamp(0.0, 1.0)
+}
+
+/// Minimal complex number, only used for the wave optics of the film.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Returns the principal square root, which has a non-negative real part.
+    fn sqrt(self) -> Self {
+        let norm = self.norm_squared().sqrt();
+        let re = ((norm + self.re) / 2.0).max(0.0).sqrt();
+        let im = ((norm - self.re) / 2.0).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    fn exp(self) -> Self {
+        let scale = self.re.exp();
+        Self::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let denominator = rhs.norm_squared();
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
@@ -0,0 +1,12 @@
+use super::{super::ray::Ray, Sampler};
+
+/// Projection from the image to the rays that leave the camera.
+pub trait Camera {
+    /// Returns the ray through the image position `(u, v)`, where `(0, 0)` is the bottom left
+    /// and `(1, 1)` the top right corner of the image. Further dimensions (e.g. the position on
+    /// the lens) are taken from the `sampler`.
+    ///
+    /// Returns `None` for positions the camera does not see, e.g. outside of the image circle of
+    /// a fisheye lens.
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray>;
+}
@@ -0,0 +1,34 @@
+use super::{
+    super::{color::Color, lights::LightSample, vec3::Vec3},
+    Sampler,
+};
+
+/// Light arriving from infinitely far away, seen by rays that leave the scene.
+pub trait Environment {
+    /// Returns the radiance arriving from `direction` (pointing away from the scene).
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    /// Returns whether the environment is sampled as a light with `sample_li`. Otherwise it only
+    /// lights the scene through rays that happen to leave it.
+    fn importance_sampled(&self) -> bool {
+        false
+    }
+
+    /// Samples a direction towards the environment.
+    fn sample_li(&self, _sampler: &mut dyn Sampler) -> Option<LightSample> {
+        None
+    }
+
+    /// Returns the density of sampling `direction` with `sample_li`.
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns the power that falls onto a disk with the radius `scene_radius` of the scene
+    /// from all directions, used to choose between the environment and the lights.
+    ///
+    /// The default is `0`, for environments that are not importance sampled.
+    fn power(&self, _scene_radius: f64) -> f64 {
+        0.0
+    }
+}
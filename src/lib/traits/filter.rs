@@ -0,0 +1,9 @@
+/// Reconstruction filter that decides how much a sample contributes to the pixels around it.
+pub trait Filter {
+    /// Returns the radius of the filter, it is zero for `|x| >= radius` or `|y| >= radius`.
+    fn radius(&self) -> f64;
+
+    /// Returns the weight of a sample at offset `(x, y)` (in pixels) from the pixel center. The
+    /// weight can be negative for filters with negative lobes.
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
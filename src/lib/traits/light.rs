@@ -0,0 +1,37 @@
+use super::{
+    super::{light_samplers::LightBounds, lights::LightSample, ray::Ray, vec3::Point3},
+    Sampler,
+};
+
+/// Source of light that can be sampled explicitly from a point in the scene.
+pub trait Light {
+    /// Samples a direction from `p` towards the light, or returns `None` if the light does not
+    /// reach `p`.
+    fn sample_li(&self, p: Point3, sampler: &mut dyn Sampler) -> Option<LightSample>;
+
+    /// Returns the density with which `sample_li` picks the direction of `r` from its origin,
+    /// for light from the primitive `primitive` of the light that `r` hits at `t`.
+    ///
+    /// The default is `0`, for lights that rays cannot hit by chance.
+    fn pdf_li(&self, _r: &Ray, _primitive: usize, _t: f64) -> f64 {
+        0.0
+    }
+
+    /// Returns the extent, power and emission directions of the light, used to choose between
+    /// lights.
+    ///
+    /// The default is `None`, for lights infinitely far away (e.g. `DirectionalLight`), which
+    /// are chosen by their `power` alone.
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    /// Returns the power of the light, used to choose between lights without bounds and the
+    /// bounded ones. Lights infinitely far away count the power that falls onto a disk with the
+    /// radius `scene_radius` of the scene.
+    ///
+    /// The default is the power of the `bounds`, or `0` without bounds.
+    fn power(&self, _scene_radius: f64) -> f64 {
+        self.bounds().map_or(0.0, |bounds| bounds.phi())
+    }
+}
@@ -1,5 +1,71 @@
-use super::super::{color::Color, hitrecord::HitRecord, ray::Ray};
+use super::{
+    super::{
+        color::Color, hitrecord::HitRecord, ray::Ray, spectrum::SampledWavelengths, vec3::Vec3,
+    },
+    Sampler,
+};
 
 pub trait Material {
-    fn scatter(&self, r_in: &Ray, hitrecord: &HitRecord) -> Option<(Color, Ray)>;
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)>;
+
+    /// Scatters a ray in spectral mode, where the attenuation holds the values at the
+    /// `wavelengths` of the path instead of red, green and blue.
+    ///
+    /// The default upsamples the attenuation of `scatter` to a spectrum. Materials that depend on
+    /// the wavelength (e.g. through dispersion) override it.
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        sampler: &mut dyn Sampler,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(Color, Ray)> {
+        let (attenuation, scattered) = self.scatter(r_in, hitrecord, sampler)?;
+        Some((wavelengths.upsample(attenuation), scattered))
+    }
+
+    /// Returns the BSDF times the absolute cosine of the angle between `wi` and the normal, for
+    /// light arriving from direction `wi` (pointing away from the surface) that scatters along
+    /// `r_in` back towards its origin. It is used to sample lights explicitly.
+    ///
+    /// The default is black, which suits materials that only scatter into discrete directions
+    /// (e.g. mirrors and smooth glass). These only receive light that `scatter` finds.
+    fn eval(&self, _r_in: &Ray, _hitrecord: &HitRecord, _wi: Vec3) -> Color {
+        Color::default()
+    }
+
+    /// Returns `eval` in spectral mode, at the `wavelengths` of the path.
+    fn eval_spectral(
+        &self,
+        r_in: &Ray,
+        hitrecord: &HitRecord,
+        wi: Vec3,
+        wavelengths: &SampledWavelengths,
+    ) -> Color {
+        wavelengths.upsample(self.eval(r_in, hitrecord, wi))
+    }
+
+    /// Returns the density with which `scatter` samples the direction `wi` (pointing away from
+    /// the surface) for a ray arriving along `r_in`, used to combine light sampling and
+    /// scattering with multiple importance sampling.
+    ///
+    /// The default is `None`, which means that (part of) the light is scattered into discrete
+    /// directions that `eval` does not include. Area and environment lights are then only found
+    /// by `scatter`, while lights from a single point or direction are still sampled.
+    fn pdf(&self, _r_in: &Ray, _hitrecord: &HitRecord, _wi: Vec3) -> Option<f64> {
+        None
+    }
+
+    /// Returns the radiance that the surface emits at `hitrecord` along `r_in` back towards its
+    /// origin.
+    ///
+    /// The default is black, for surfaces that do not glow.
+    fn emitted(&self, _r_in: &Ray, _hitrecord: &HitRecord) -> Color {
+        Color::default()
+    }
 }
@@ -1,5 +1,17 @@
+mod camera;
+mod environment;
+mod filter;
 mod hittable;
+mod light;
 mod material;
+mod sampler;
+mod texture;
 
+pub use camera::Camera;
+pub use environment::Environment;
+pub use filter::Filter;
 pub use hittable::Hittable;
+pub use light::Light;
 pub use material::Material;
+pub use sampler::Sampler;
+pub use texture::{FloatTexture, Texture};
@@ -0,0 +1,21 @@
+/// Source of sample values in the `[0, 1)` range for everything that is decided randomly while
+/// tracing a path (pixel position, lens position, bounce directions, ...).
+///
+/// Every call to `get_1d` or `get_2d` consumes the next sample _dimension_. Because a path always
+/// asks for its dimensions in the same order, a sampler can distribute the values of a single
+/// dimension well over all samples of a pixel.
+pub trait Sampler {
+    /// Number of samples the sampler is tuned for. Sample indices past this number are allowed,
+    /// but are not guaranteed to be as well distributed.
+    fn samples_per_pixel(&self) -> usize;
+
+    /// Prepares the sampler for generating the `sample_index`-th sample of the `pixel` and resets
+    /// the sample dimension.
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize);
+
+    /// Returns the value for the next sample dimension.
+    fn get_1d(&mut self) -> f64;
+
+    /// Returns the values for the next two sample dimensions.
+    fn get_2d(&mut self) -> (f64, f64);
+}
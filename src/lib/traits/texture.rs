@@ -0,0 +1,13 @@
+use super::super::{color::Color, vec3::Point3};
+
+/// Color that varies over the surface of an object.
+pub trait Texture {
+    /// Returns the color at surface coordinates `uv` and point `p` of the surface.
+    fn value(&self, uv: (f64, f64), p: Point3) -> Color;
+}
+
+/// Scalar material parameter (e.g. roughness) that varies over the surface of an object.
+pub trait FloatTexture {
+    /// Returns the value at surface coordinates `uv` and point `p` of the surface.
+    fn value(&self, uv: (f64, f64), p: Point3) -> f64;
+}
@@ -12,3 +12,67 @@ pub fn random_double_range(range: Range<f64>) -> f64 {
     let mut rng = rand::thread_rng();
     rng.gen_range(range)
 }
+
+/// Largest `f64` that is strictly smaller than 1. Used to keep sample values in the `[0, 1)` range.
+pub const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Scrambles the bits of a `u64` so that small differences in the input result in large
+/// differences in the output (finalizer of `SplitMix64`).
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+/// Returns a hash of all values in the slice.
+pub fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e37_79b9_7f4a_7c15, |h, &v| mix_bits(h ^ mix_bits(v)))
+}
+
+/// Turns a hash into a `f64` between 0 and 1 (exclusive for 1).
+pub fn hash_to_double(hash: u64) -> f64 {
+    (hash >> 11) as f64 * (1.0 / (1_u64 << 53) as f64)
+}
+
+/// Small and deterministic random number generator (<https://www.pcg-random.org>). Unlike
+/// `random_double` it can be seeded, which makes it possible to reproduce the exact same samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: 0xda3e_39cb_94b9_5bdb,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(0x5851_f42d_4c95_7f2d)
+            .wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Returns a random `f64` between 0 and 1
+    /// Note: the range is exclusive for 1
+    pub fn next_double(&mut self) -> f64 {
+        let bits = ((self.next_u32() as u64) << 32) | self.next_u32() as u64;
+        hash_to_double(bits)
+    }
+}
@@ -1,24 +1,44 @@
-#![allow(dead_code)]
+#![allow(dead_code, special_module_name)]
 
 use crate::lib::{
+    adaptive,
+    cameras::{
+        CameraKind, CubeMapCamera, EquirectangularCamera, FisheyeCamera, StereoCamera,
+        ThinLensCamera,
+    },
+    environment::{EnvironmentMap, PhysicalSky},
+    film::{Film, SampleSpace},
     hittables::{HittableList, Sphere},
+    lights::LightList,
     materials::{Dielectric, Lambertian, Metal},
-    traits::Hittable,
-    utils, Camera, Color, Point3, Ray, Vec3,
+    options::RenderOptions,
+    sampling,
+    scheduler::{Region, TileScheduler},
+    spectrum::{Ior, SampledWavelengths},
+    traits::{Camera, Hittable, Sampler},
+    Color, HitRecord, Point3, Ray, Vec3,
 };
-use std::rc::Rc;
+use std::{io, rc::Rc};
 
 mod lib;
 
 fn main() {
+    let options = RenderOptions::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    });
+
     // ===================
     //        Image
     // ===================
 
-    let aspect_ratio = 16.0 / 9.0;
+    let eye_aspect_ratio = options.camera.aspect_ratio().unwrap_or(16.0 / 9.0);
+    let aspect_ratio = match options.stereo {
+        Some(layout) => layout.aspect_ratio(eye_aspect_ratio),
+        None => eye_aspect_ratio,
+    };
     let image_width = 2000_usize;
     let image_height = (image_width as f64 / aspect_ratio).round() as usize;
-    let samples_per_pixel = 200_usize;
     let max_ray_depth = 100_usize;
 
     // ===================
@@ -30,7 +50,7 @@ fn main() {
     // materials
     let material_ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
     let material_center = Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let material_left = Rc::new(Dielectric::new(1.5));
+    let material_left = Rc::new(Dielectric::with_ior(Ior::bk7()));
     let material_right = Rc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
 
     // objects
@@ -60,6 +80,30 @@ fn main() {
         material_right.clone(),
     )));
 
+    // lights
+    let mut lights = LightList::default();
+    lights.set_light_sampler(options.light_sampler);
+    // the three spheres and the ground below them
+    lights.set_scene_radius(2.0);
+    if let Some(sun) = options.sun {
+        let albedo = options.ground_albedo;
+        lights.set_environment(Box::new(
+            PhysicalSky::new(sun.elevation, sun.azimuth, options.turbidity)
+                .ground_albedo(Color::new(albedo, albedo, albedo)),
+        ));
+    }
+    if let Some(path) = &options.environment {
+        let environment = EnvironmentMap::open(path).unwrap_or_else(|e| {
+            eprintln!("error: could not load environment: {}", e);
+            std::process::exit(1);
+        });
+        lights.set_environment(Box::new(
+            environment
+                .rotation(options.environment_rotation)
+                .intensity(options.environment_intensity),
+        ));
+    }
+
     // ===================
     //       Camera
     // ===================
@@ -70,54 +114,247 @@ fn main() {
     let dist_to_focus = (lookat - origin).length();
     let aperture = 0.7;
 
-    let cam = Camera::new_focusable(
-        origin,
-        lookat,
-        vup,
-        20.0,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-    );
+    let convergence_distance = options.convergence_distance.unwrap_or(dist_to_focus);
+    let cam: Box<dyn Camera> = match (options.camera, options.orthographic) {
+        (CameraKind::Equirectangular, _) => {
+            let cam = EquirectangularCamera::new(origin, lookat, vup);
+            match options.stereo {
+                Some(layout) => {
+                    Box::new(StereoCamera::new(cam.ods_pair(options.interocular), layout))
+                }
+                None => Box::new(cam),
+            }
+        }
+        (CameraKind::Fisheye(projection), _) => Box::new(FisheyeCamera::new(
+            origin,
+            lookat,
+            vup,
+            projection,
+            options.fisheye_fov,
+            eye_aspect_ratio,
+        )),
+        (CameraKind::CubeMap, _) => Box::new(CubeMapCamera::new(origin)),
+        (CameraKind::Perspective, Some(view)) => Box::new(ThinLensCamera::new_orthographic(
+            origin,
+            lookat,
+            vup,
+            view,
+            eye_aspect_ratio,
+            aperture,
+            dist_to_focus,
+        )),
+        (CameraKind::Perspective, None) => {
+            let cam = ThinLensCamera::new_focusable(
+                origin,
+                lookat,
+                vup,
+                20.0,
+                eye_aspect_ratio,
+                aperture,
+                dist_to_focus,
+            );
+            match options.stereo {
+                Some(layout) => Box::new(StereoCamera::new(
+                    cam.stereo_pair(
+                        options.interocular,
+                        convergence_distance,
+                        options.convergence,
+                    ),
+                    layout,
+                )),
+                None => Box::new(cam),
+            }
+        }
+    };
 
     // ===================
     //       Render
     // ===================
 
-    // print file to console (https://en.wikipedia.org/wiki/Netpbm#PPM_example)
-    // file header
-    println!("P3\n{} {}\n255", image_width, image_height);
+    // the sampler is tuned for a single pass, so every pass adds a well distributed set of samples
+    let samples_per_pixel = options.samples_per_pixel;
+    let min_samples_per_pixel = options.min_samples_per_pixel.min(samples_per_pixel);
+    let pass_samples_per_pixel = options.pass_samples_per_pixel.unwrap_or(samples_per_pixel);
+    let mut sampler = options.sampler.build(pass_samples_per_pixel, options.seed);
 
-    // example image
-    for j in (0..image_height).rev() {
-        // print progress to `stderr`
-        // ascii escape sequence that rerenders said line
-        eprintln!("                                                 \x1B[1A"); // temporary fix for trailing
-        eprintln!("Scanline: {} / {}\x1B[1A", image_height - j, image_height);
+    let region = options
+        .region
+        .unwrap_or_else(|| Region::full(image_width, image_height));
+    if !region.inside(&Region::full(image_width, image_height)) {
+        eprintln!(
+            "error: region is outside the {}x{} image",
+            image_width, image_height
+        );
+        std::process::exit(1);
+    }
+    let filter = options.filter.build(options.filter_radius);
+    let space = match options.spectral {
+        true => SampleSpace::Xyz,
+        false => SampleSpace::Rgb,
+    };
 
-        for i in 0..image_width {
-            // use `Vec3` because the `Color` invariants cannot be guaranteed before rescaling
-            let mut color = Vec3::default();
+    let mut film = match &options.resume {
+        Some(path) => Film::load_checkpoint(path, filter).unwrap_or_else(|e| {
+            eprintln!("error: could not load checkpoint: {}", e);
+            std::process::exit(1);
+        }),
+        None => Film::new(image_width, image_height, region, filter, space),
+    };
+    if film.width() != image_width || film.height() != image_height || film.region() != region {
+        eprintln!("error: checkpoint resolution or region does not match the render");
+        std::process::exit(1);
+    }
+    if film.space() != space {
+        eprintln!("error: checkpoint was not rendered with the same `--spectral` setting");
+        std::process::exit(1);
+    }
 
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + utils::random_double()) / (image_width - 1) as f64;
-                let v = (j as f64 + utils::random_double()) / (image_height - 1) as f64;
+    // also render the pixels around the region that contribute to it through the filter
+    let scheduler = TileScheduler::new(
+        image_width,
+        image_height,
+        film.sample_region(),
+        options.tile_size,
+        options.tile_order,
+    );
+
+    // render passes until every pixel has all of its samples (or has converged)
+    let mut pass = 0;
+    loop {
+        pass += 1;
+        let mut sampled = false;
+
+        for (k, tile) in scheduler.tiles().iter().enumerate() {
+            // print progress to `stderr`
+            // ascii escape sequence that rerenders said line
+            eprintln!("                                                 \x1B[1A"); // temporary fix for trailing
+            eprintln!(
+                "Pass: {}, Tile: {} / {}\x1B[1A",
+                pass,
+                k + 1,
+                scheduler.tiles().len()
+            );
+
+            for y in tile.y0..tile.y1 {
+                // the camera has its origin in the bottom left corner
+                let j = image_height - 1 - y;
+
+                for i in tile.x0..tile.x1 {
+                    let pass_end =
+                        (film.pixel(i, j).count() + pass_samples_per_pixel).min(samples_per_pixel);
+
+                    loop {
+                        let stats = film.pixel(i, j);
+                        if stats.count() >= pass_end {
+                            break;
+                        }
 
-                let r = cam.get_ray(u, v);
-                color += ray_color(&r, &world, max_ray_depth).into(); // because `color` is a `Vec3` for now
+                        // stop sampling pixels that have converged
+                        if let Some(threshold) = options.adaptive_threshold {
+                            if stats.count() >= min_samples_per_pixel && stats.converged(threshold)
+                            {
+                                break;
+                            }
+                        }
+
+                        sampler.start_pixel_sample((i, j), stats.count());
+
+                        let (du, dv) = sampler.get_2d();
+                        let u = (i as f64 + du) / (image_width - 1) as f64;
+                        let v = (j as f64 + dv) / (image_height - 1) as f64;
+
+                        let color = match options.spectral {
+                            true => {
+                                let mut wavelengths =
+                                    SampledWavelengths::sample_visible(sampler.get_1d());
+                                match cam.get_ray(u, v, sampler.as_mut()) {
+                                    Some(r) => {
+                                        let radiance = ray_color(
+                                            &r,
+                                            &world,
+                                            &lights,
+                                            max_ray_depth,
+                                            None,
+                                            sampler.as_mut(),
+                                            Some(&mut wavelengths),
+                                        );
+                                        wavelengths.xyz(radiance)
+                                    }
+                                    None => Color::default(),
+                                }
+                            }
+                            false => match cam.get_ray(u, v, sampler.as_mut()) {
+                                Some(r) => ray_color(
+                                    &r,
+                                    &world,
+                                    &lights,
+                                    max_ray_depth,
+                                    None,
+                                    sampler.as_mut(),
+                                    None,
+                                ),
+                                None => Color::default(),
+                            },
+                        };
+                        film.add_sample(i, j, (du, dv), color);
+                        sampled = true;
+                    }
+                }
             }
+        }
 
-            let color: Color = (color / samples_per_pixel as f64)
-                .try_into()
-                .expect("could not convert `Vec3` to `Color`");
-            println!("{}", color.write_color());
+        let finished = !sampled;
+        if finished || pass % options.save_every == 0 {
+            if let Some(path) = &options.checkpoint {
+                film.save_checkpoint(path)
+                    .unwrap_or_else(|e| eprintln!("\ncould not save checkpoint: {}", e));
+            }
         }
+        if finished {
+            break;
+        }
+        if pass % options.save_every == 0 {
+            if let Some(path) = &options.preview {
+                film.save_ppm(path, &options.display)
+                    .unwrap_or_else(|e| eprintln!("\ncould not write preview: {}", e));
+            }
+        }
+    }
+
+    // print file to console
+    film.write_ppm(&mut io::stdout().lock(), &options.display)
+        .expect("could not write image to `stdout`");
+
+    if let Some(path) = &options.heatmap {
+        // like the image, the heatmap only covers the rendered region
+        adaptive::write_heatmap(
+            path,
+            region.width(),
+            region.height(),
+            &film.sample_counts(),
+            samples_per_pixel,
+        )
+        .unwrap_or_else(|e| eprintln!("\ncould not write heatmap: {}", e));
     }
 
     eprintln!("\nDone");
 }
 
-fn ray_color<T: Hittable>(r: &Ray, world: &T, depth: usize) -> Color {
+/// Returns the radiance arriving along `r`, in RGB or, with `wavelengths`, at the wavelengths of
+/// the path.
+///
+/// `scatter_pdf` is the density with which the previous surface scattered `r`, used to weigh
+/// emitters and the environment against light sampling. It is `None` for camera rays and discrete
+/// directions.
+fn ray_color<T: Hittable>(
+    r: &Ray,
+    world: &T,
+    lights: &LightList,
+    depth: usize,
+    scatter_pdf: Option<f64>,
+    sampler: &mut dyn Sampler,
+    mut wavelengths: Option<&mut SampledWavelengths>,
+) -> Color {
     // check if depth limit is reached
     if depth == 0 {
         return Color::new(0.0, 0.0, 0.0);
@@ -125,15 +362,101 @@ fn ray_color<T: Hittable>(r: &Ray, world: &T, depth: usize) -> Color {
 
     // fix the `shadow acne` problem by ignoring bounces that bounce from themselves
     if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        if let Some((attenuation, scattered)) = rec.mat().scatter(r, &rec) {
-            return attenuation * ray_color(&scattered, world, depth - 1);
+        // emission that light sampling finds as well is weighted down
+        let mut emitted = rec.mat().emitted(r, &rec);
+        if emitted != Color::default() {
+            if let Some(pdf) = scatter_pdf {
+                emitted *= sampling::power_heuristic(pdf, lights.pdf(r, &rec));
+            }
+            if let Some(wavelengths) = wavelengths.as_deref() {
+                emitted = wavelengths.upsample(emitted);
+            }
+        }
+
+        let direct = match lights.is_empty() {
+            true => Color::default(),
+            false => sample_lights(r, &rec, world, lights, sampler, wavelengths.as_deref()),
+        };
+
+        let scattered = match wavelengths.as_deref_mut() {
+            Some(wavelengths) => rec.mat().scatter_spectral(r, &rec, sampler, wavelengths),
+            None => rec.mat().scatter(r, &rec, sampler),
+        };
+        if let Some((attenuation, scattered)) = scattered {
+            let scatter_pdf = match lights.is_empty() {
+                true => None,
+                false => rec.mat().pdf(r, &rec, scattered.direction()),
+            };
+            let indirect = ray_color(
+                &scattered,
+                world,
+                lights,
+                depth - 1,
+                scatter_pdf,
+                sampler,
+                wavelengths,
+            );
+            return emitted + direct + attenuation * indirect;
         }
+        return emitted + direct;
+    }
+
+    // the part of the environment that is also found by light sampling is weighted down
+    let weight = match scatter_pdf {
+        Some(pdf) => sampling::power_heuristic(pdf, lights.environment_pdf(r)),
+        None => 1.0,
+    };
+    let sky = lights.environment().radiance(r.direction()) * weight;
+    match wavelengths {
+        Some(wavelengths) => wavelengths.upsample(sky),
+        None => sky,
+    }
+}
+
+/// Returns the light that arrives directly from a sampled light and scatters at `rec` along `r`
+/// back towards its origin, in RGB or, with `wavelengths`, at the wavelengths of the path.
+fn sample_lights<T: Hittable>(
+    r: &Ray,
+    rec: &HitRecord,
+    world: &T,
+    lights: &LightList,
+    sampler: &mut dyn Sampler,
+    wavelengths: Option<&SampledWavelengths>,
+) -> Color {
+    let Some(sample) = lights.sample(rec.get_inpact_point(), sampler) else {
+        return Color::default();
+    };
+
+    let (f, radiance) = match wavelengths {
+        Some(wavelengths) => (
+            rec.mat().eval_spectral(r, rec, sample.wi, wavelengths),
+            wavelengths.upsample(sample.radiance),
+        ),
+        None => (rec.mat().eval(r, rec, sample.wi), sample.radiance),
+    };
+    if f == Color::default() || sample.pdf <= 0.0 {
+        return Color::default();
+    }
+
+    // lights that scattered rays can hit as well are weighted against scattering
+    let weight = match sample.delta {
+        true => 1.0,
+        false => match rec.mat().pdf(r, rec, sample.wi) {
+            Some(scatter_pdf) => sampling::power_heuristic(sample.pdf, scatter_pdf),
+            None => return Color::default(),
+        },
+    };
+
+    // shadow ray, that stops just short of the light
+    let shadow = Ray::new(rec.get_inpact_point(), sample.wi);
+    if world
+        .hit(&shadow, 0.001, sample.distance * (1.0 - 1.0e-6))
+        .is_some()
+    {
         return Color::default();
     }
 
-    let unit_dir = r.direction().unit_vector();
-    let t = 0.5 * (unit_dir.y() + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+    f * radiance * (weight / sample.pdf)
 }
 
 fn hit_sphere(center: &Point3, radius: f64, r: &Ray) -> Option<f64> {